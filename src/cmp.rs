@@ -3,10 +3,16 @@
 // For the full copyright and license information, please view the LICENSE-*
 // files that was distributed with this source code.
 
-use crate::utils::format_failure_to_read_input_file;
+#[cfg(feature = "mmap")]
+use crate::utils::map_file;
+use crate::utils::visible::{write_visible, ByteDisplay};
+use crate::utils::{
+    format_failure_to_read_input_file, is_posixly_correct, parse_byte_count, print_version,
+    EXIT_STATUS_HELP,
+};
 use std::env::{self, ArgsOs};
 use std::ffi::OsString;
-use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::iter::Peekable;
 use std::process::ExitCode;
 use std::{cmp, fs, io};
@@ -17,9 +23,6 @@ use std::os::fd::{AsRawFd, FromRawFd};
 #[cfg(not(target_os = "windows"))]
 use std::os::unix::fs::MetadataExt;
 
-#[cfg(target_os = "windows")]
-use std::os::windows::fs::MetadataExt;
-
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct Params {
     executable: OsString,
@@ -31,11 +34,46 @@ pub struct Params {
     max_bytes: Option<usize>,
     verbose: bool,
     quiet: bool,
+    total: bool,
+    strip_trailing_cr: bool,
 }
 
 #[inline]
 fn usage_string(executable: &str) -> String {
-    format!("Usage: {} <from> <to>", executable)
+    format!("Usage: {executable} [OPTION]... FILE1 [FILE2 [SKIP1 [SKIP2]]]")
+}
+
+fn help_string(executable: &str) -> String {
+    let usage = usage_string(executable);
+    format!(
+        "{usage}
+Compare two files byte by byte.
+
+The optional SKIP1 and SKIP2 specify the number of bytes to skip
+at the beginning of each file (zero by default).
+
+Mandatory arguments to long options are mandatory for short options too.
+  -b, --print-bytes          print differing bytes
+  -i, --ignore-initial=SKIP         skip first SKIP bytes of both inputs
+  -i, --ignore-initial=SKIP1:SKIP2  skip first SKIP1 bytes of FILE1 and
+                                      first SKIP2 bytes of FILE2
+  -l, --verbose              output byte numbers and differing byte values
+  -n, --bytes=LIMIT          compare at most LIMIT bytes
+  -s, --quiet, --silent      suppress all normal output
+  -T, --total                print the number of bytes compared and differing
+      --strip-trailing-cr    ignore a carriage return directly before a newline;
+                               other carriage returns are still compared, and
+                               byte numbers count only the bytes compared
+      --help                 display this help and exit
+  -v, --version              output version information and exit
+
+SKIP values may be followed by the following multiplicative suffixes:
+kB 1000, K 1024, MB 1,000,000, M 1,048,576,
+GB 1,000,000,000, G 1,073,741,824, and so on for T, P, E, Z, Y.
+
+If a FILE is '-' or missing, read standard input.
+{EXIT_STATUS_HELP}"
+    )
 }
 
 #[cfg(not(target_os = "windows"))]
@@ -62,60 +100,14 @@ fn is_stdout_dev_null() -> bool {
 
 pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Result<Params, String> {
     let Some(executable) = opts.next() else {
-        return Err("Usage: <exe> <from> <to>".to_string());
+        return Err(usage_string("<exe>"));
     };
     let executable_str = executable.to_string_lossy().to_string();
 
-    let parse_skip = |param: &str, skip_desc: &str| -> Result<usize, String> {
-        let suffix_start = param
-            .find(|b: char| !b.is_ascii_digit())
-            .unwrap_or(param.len());
-        let mut num = match param[..suffix_start].parse::<usize>() {
-            Ok(num) => num,
-            Err(e) if *e.kind() == std::num::IntErrorKind::PosOverflow => usize::MAX,
-            Err(_) => {
-                return Err(format!(
-                    "{}: invalid --ignore-initial value '{}'",
-                    executable_str, skip_desc
-                ))
-            }
-        };
-
-        if suffix_start != param.len() {
-            // Note that GNU cmp advertises supporting up to Y, but fails if you try
-            // to actually use anything beyond E.
-            let multiplier: usize = match &param[suffix_start..] {
-                "kB" => 1_000,
-                "K" => 1_024,
-                "MB" => 1_000_000,
-                "M" => 1_048_576,
-                "GB" => 1_000_000_000,
-                "G" => 1_073_741_824,
-                "TB" => 1_000_000_000_000,
-                "T" => 1_099_511_627_776,
-                "PB" => 1_000_000_000_000_000,
-                "P" => 1_125_899_906_842_624,
-                "EB" => 1_000_000_000_000_000_000,
-                "E" => 1_152_921_504_606_846_976,
-                "ZB" => usize::MAX, // 1_000_000_000_000_000_000_000,
-                "Z" => usize::MAX,  // 1_180_591_620_717_411_303_424,
-                "YB" => usize::MAX, // 1_000_000_000_000_000_000_000_000,
-                "Y" => usize::MAX,  // 1_208_925_819_614_629_174_706_176,
-                _ => {
-                    return Err(format!(
-                        "{}: invalid --ignore-initial value '{}'",
-                        executable_str, skip_desc
-                    ));
-                }
-            };
-
-            num = match num.overflowing_mul(multiplier) {
-                (n, false) => n,
-                _ => usize::MAX,
-            }
-        }
-
-        Ok(num)
+    // Errors name `option` and quote `desc`, the whole value given to it.
+    let parse_skip = |param: &str, desc: &str, option: &str| -> Result<usize, String> {
+        parse_byte_count(param)
+            .map_err(|_| format!("{executable_str}: invalid {option} value '{desc}'"))
     };
 
     let mut params = Params {
@@ -126,6 +118,7 @@ pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Resu
     let mut to = None;
     let mut skip_pos1 = None;
     let mut skip_pos2 = None;
+    let posixly_correct = is_posixly_correct();
     while let Some(param) = opts.next() {
         if param == "--" {
             break;
@@ -133,6 +126,9 @@ pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Resu
         if param == "-" {
             if from.is_none() {
                 from = Some(param);
+                if posixly_correct {
+                    break;
+                }
             } else if to.is_none() {
                 to = Some(param);
             } else {
@@ -165,16 +161,7 @@ pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Resu
                 let (_, arg) = param_str.split_once('=').unwrap();
                 arg.to_string()
             };
-            let max_bytes = match max_bytes.parse::<usize>() {
-                Ok(num) => num,
-                Err(e) if *e.kind() == std::num::IntErrorKind::PosOverflow => usize::MAX,
-                Err(_) => {
-                    return Err(format!(
-                        "{}: invalid --bytes value '{}'",
-                        executable_str, max_bytes
-                    ))
-                }
-            };
+            let max_bytes = parse_skip(&max_bytes, &max_bytes, "--bytes")?;
             params.max_bytes = Some(max_bytes);
             continue;
         }
@@ -190,11 +177,11 @@ pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Resu
             };
             let (skip_a, skip_b) = if let Some((skip_a, skip_b)) = skip_desc.split_once(':') {
                 (
-                    parse_skip(skip_a, &skip_desc)?,
-                    parse_skip(skip_b, &skip_desc)?,
+                    parse_skip(skip_a, &skip_desc, "--ignore-initial")?,
+                    parse_skip(skip_b, &skip_desc, "--ignore-initial")?,
                 )
             } else {
-                let skip = parse_skip(&skip_desc, &skip_desc)?;
+                let skip = parse_skip(&skip_desc, &skip_desc, "--ignore-initial")?;
                 (skip, skip)
             };
             params.skip_a = Some(skip_a);
@@ -205,8 +192,20 @@ pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Resu
             params.quiet = true;
             continue;
         }
+        if param == "-T" || param == "--total" {
+            params.total = true;
+            continue;
+        }
+        if param == "--strip-trailing-cr" {
+            params.strip_trailing_cr = true;
+            continue;
+        }
         if param == "--help" {
-            println!("{}", usage_string(&executable_str));
+            println!("{}", help_string(&executable_str));
+            std::process::exit(0);
+        }
+        if param == "-v" || param == "--version" {
+            print_version("cmp");
             std::process::exit(0);
         }
         if param_str.starts_with('-') {
@@ -214,12 +213,15 @@ pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Resu
         }
         if from.is_none() {
             from = Some(param);
+            if posixly_correct {
+                break;
+            }
         } else if to.is_none() {
             to = Some(param);
         } else if skip_pos1.is_none() {
-            skip_pos1 = Some(parse_skip(&param_str, &param_str)?);
+            skip_pos1 = Some(parse_skip(&param_str, &param_str, "--ignore-initial")?);
         } else if skip_pos2.is_none() {
-            skip_pos2 = Some(parse_skip(&param_str, &param_str)?);
+            skip_pos2 = Some(parse_skip(&param_str, &param_str, "--ignore-initial")?);
         } else {
             return Err(usage_string(&executable_str));
         }
@@ -262,7 +264,7 @@ pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Resu
             params.skip_a = skip_pos1;
         } else if let Some(param) = opts.next() {
             let param_str = param.to_string_lossy().to_string();
-            params.skip_a = Some(parse_skip(&param_str, &param_str)?);
+            params.skip_a = Some(parse_skip(&param_str, &param_str, "--ignore-initial")?);
         }
     };
     if params.skip_b.is_none() {
@@ -270,7 +272,7 @@ pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Resu
             params.skip_b = skip_pos2;
         } else if let Some(param) = opts.next() {
             let param_str = param.to_string_lossy().to_string();
-            params.skip_b = Some(parse_skip(&param_str, &param_str)?);
+            params.skip_b = Some(parse_skip(&param_str, &param_str, "--ignore-initial")?);
         }
     }
 
@@ -281,12 +283,42 @@ fn prepare_reader(
     path: &OsString,
     skip: &Option<usize>,
     params: &Params,
+) -> Result<Box<dyn BufRead>, String> {
+    let reader = open_reader(path, skip, params)?;
+    if params.strip_trailing_cr {
+        return Ok(Box::new(StripCrReader::new(reader)));
+    }
+    Ok(reader)
+}
+
+fn open_reader(
+    path: &OsString,
+    skip: &Option<usize>,
+    params: &Params,
 ) -> Result<Box<dyn BufRead>, String> {
     let mut reader: Box<dyn BufRead> = if path == "-" {
         Box::new(BufReader::new(io::stdin()))
     } else {
         match fs::File::open(path) {
-            Ok(file) => Box::new(BufReader::new(file)),
+            Ok(mut file) => {
+                #[cfg(feature = "mmap")]
+                if let Some(map) = map_file(&file) {
+                    // Skip by starting further into the map rather than
+                    // reading the skipped bytes.
+                    let pos = skip.map_or(0, |skip| cmp::min(skip, map.len()));
+                    return Ok(Box::new(MappedReader { map, pos }));
+                }
+                // Likewise seek past the skipped bytes of a regular file;
+                // pipes and devices fall through to reading them.
+                if let Some(skip) = skip {
+                    if file.metadata().is_ok_and(|metadata| metadata.is_file())
+                        && file.seek(SeekFrom::Start(*skip as u64)).is_ok()
+                    {
+                        return Ok(Box::new(BufReader::new(file)));
+                    }
+                }
+                Box::new(BufReader::new(file))
+            }
             Err(e) => {
                 return Err(format_failure_to_read_input_file(
                     &params.executable,
@@ -310,6 +342,103 @@ fn prepare_reader(
     Ok(reader)
 }
 
+// A reader over a memory-mapped file, which hands out the rest of the file
+// as a single buffer so that the fast path compares it in one go.
+#[cfg(feature = "mmap")]
+struct MappedReader {
+    map: memmap2::Mmap,
+    pos: usize,
+}
+
+#[cfg(feature = "mmap")]
+impl Read for MappedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = (&self.map[self.pos..]).read(buf)?;
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl BufRead for MappedReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(&self.map[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = cmp::min(self.pos + amt, self.map.len());
+    }
+}
+
+// A reader that drops each carriage return directly followed by a newline,
+// for --strip-trailing-cr. A carriage return ending a buffer is held back
+// until the next one shows whether a newline follows it. The dropped bytes
+// are not counted, so reported offsets are in the stripped input, which are
+// the same for both files up to the first difference.
+struct StripCrReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    pos: usize,
+    pending_cr: bool,
+}
+
+impl<R: BufRead> StripCrReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+            pos: 0,
+            pending_cr: false,
+        }
+    }
+}
+
+impl<R: BufRead> Read for StripCrReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = (&self.fill_buf()?[..]).read(buf)?;
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for StripCrReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.pos == self.buf.len() {
+            self.buf.clear();
+            self.pos = 0;
+            let chunk = self.inner.fill_buf()?;
+            if chunk.is_empty() {
+                if self.pending_cr {
+                    self.pending_cr = false;
+                    self.buf.push(b'\r');
+                }
+                break;
+            }
+            if self.pending_cr && chunk[0] != b'\n' {
+                self.buf.push(b'\r');
+            }
+            let (chunk, pending_cr) = match chunk.strip_suffix(b"\r") {
+                Some(chunk) => (chunk, true),
+                None => (chunk, false),
+            };
+            let mut bytes = chunk.iter().peekable();
+            while let Some(&byte) = bytes.next() {
+                if byte != b'\r' || bytes.peek() != Some(&&b'\n') {
+                    self.buf.push(byte);
+                }
+            }
+            self.pending_cr = pending_cr;
+            let len = chunk.len() + usize::from(pending_cr);
+            self.inner.consume(len);
+        }
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = cmp::min(self.pos + amt, self.buf.len());
+    }
+}
+
 #[derive(Debug)]
 pub enum Cmp {
     Equal,
@@ -320,26 +449,42 @@ pub fn cmp(params: &Params) -> Result<Cmp, String> {
     let mut from = prepare_reader(&params.from, &params.skip_a, params)?;
     let mut to = prepare_reader(&params.to, &params.skip_b, params)?;
 
-    let mut offset_width = params.max_bytes.unwrap_or(usize::MAX);
-
-    if let (Ok(a_meta), Ok(b_meta)) = (fs::metadata(&params.from), fs::metadata(&params.to)) {
-        #[cfg(not(target_os = "windows"))]
-        let (a_size, b_size) = (a_meta.size(), b_meta.size());
-
-        #[cfg(target_os = "windows")]
-        let (a_size, b_size) = (a_meta.file_size(), b_meta.file_size());
-
-        // If the files have different sizes, we already know they are not identical. If we have not
-        // been asked to show even the first difference, we can quit early.
-        if params.quiet && a_size != b_size {
-            return Ok(Cmp::Different);
+    // The bytes left to compare in each input that is a regular file: those
+    // past the skip, and at most --bytes. Pipes and stdin give no bound.
+    let bytes_left = |path: &OsString, skip: Option<usize>| {
+        if path == "-" {
+            return None;
         }
-
-        let smaller = cmp::min(a_size, b_size) as usize;
-        offset_width = cmp::min(smaller, offset_width);
+        let meta = fs::metadata(path).ok().filter(fs::Metadata::is_file)?;
+        let size = usize::try_from(meta.len()).unwrap_or(usize::MAX);
+        let left = size.saturating_sub(skip.unwrap_or(0));
+        Some(
+            params
+                .max_bytes
+                .map_or(left, |max_bytes| cmp::min(left, max_bytes)),
+        )
+    };
+    let from_left = bytes_left(&params.from, params.skip_a);
+    let to_left = bytes_left(&params.to, params.skip_b);
+
+    // If the inputs have different lengths to compare, we already know they are not identical,
+    // unless carriage returns are stripped. If we have not been asked to show even the first
+    // difference, we can quit early.
+    if params.quiet
+        && !params.strip_trailing_cr
+        && from_left.zip(to_left).is_some_and(|(from, to)| from != to)
+    {
+        return Ok(Cmp::Different);
     }
 
-    let offset_width = 1 + offset_width.checked_ilog10().unwrap_or(1) as usize;
+    // As in GNU cmp, byte numbers in the -l listing are padded to the width of
+    // the largest one: at most the bytes left in the smaller regular file, and
+    // at most --bytes.
+    let max_byte = [from_left, to_left]
+        .into_iter()
+        .flatten()
+        .fold(params.max_bytes.unwrap_or(usize::MAX), cmp::min);
+    let offset_width = max_byte.checked_ilog10().map_or(1, |log| log as usize + 1);
 
     // Capacity calc: at_byte width + 2 x 3-byte octal numbers + 2 x 4-byte value + 4 spaces
     let mut output = Vec::<u8>::with_capacity(offset_width + 3 * 2 + 4 * 2 + 4);
@@ -347,9 +492,19 @@ pub fn cmp(params: &Params) -> Result<Cmp, String> {
     let mut at_byte = 1;
     let mut at_line = 1;
     let mut start_of_line = true;
+    let mut differing = 0;
     let mut stdout = BufWriter::new(io::stdout().lock());
     let mut compare = Cmp::Equal;
     loop {
+        // Stop once --bytes have been compared; with -n 0 that is before
+        // reading anything, and the inputs are equal.
+        if params
+            .max_bytes
+            .is_some_and(|max_bytes| at_byte > max_bytes)
+        {
+            break;
+        }
+
         // Fill up our buffers.
         let from_buf = match from.fill_buf() {
             Ok(buf) => buf,
@@ -386,6 +541,7 @@ pub fn cmp(params: &Params) -> Result<Cmp, String> {
             };
 
             report_eof(at_byte, at_line, start_of_line, eof_on, params);
+            report_total(&mut stdout, at_byte, differing, params);
             return Ok(Cmp::Different);
         }
 
@@ -412,11 +568,22 @@ pub fn cmp(params: &Params) -> Result<Cmp, String> {
             continue;
         }
 
+        // In quiet mode the position of the difference is not reported, so
+        // there is no need to look for it, unless it may lie past --bytes.
+        if params.quiet
+            && params
+                .max_bytes
+                .is_none_or(|max_bytes| at_byte + consumed - 1 <= max_bytes)
+        {
+            return Ok(Cmp::Different);
+        }
+
         // Iterate over the buffers, the zip iterator will stop us as soon as the
         // first one runs out.
         for (&from_byte, &to_byte) in from_buf.iter().zip(to_buf.iter()) {
             if from_byte != to_byte {
                 compare = Cmp::Different;
+                differing += 1;
 
                 if params.verbose {
                     format_verbose_difference(
@@ -436,6 +603,7 @@ pub fn cmp(params: &Params) -> Result<Cmp, String> {
                     output.clear();
                 } else {
                     report_difference(from_byte, to_byte, at_byte, at_line, params);
+                    report_total(&mut stdout, at_byte + 1, differing, params);
                     return Ok(Cmp::Different);
                 }
             }
@@ -457,8 +625,16 @@ pub fn cmp(params: &Params) -> Result<Cmp, String> {
         // Notify our readers about the bytes we went over.
         from.consume(consumed);
         to.consume(consumed);
+
+        if params
+            .max_bytes
+            .is_some_and(|max_bytes| at_byte > max_bytes)
+        {
+            break;
+        }
     }
 
+    report_total(&mut stdout, at_byte, differing, params);
     Ok(compare)
 }
 
@@ -494,12 +670,6 @@ pub fn main(opts: Peekable<ArgsOs>) -> ExitCode {
     }
 }
 
-#[inline]
-fn is_ascii_printable(byte: u8) -> bool {
-    let c = byte as char;
-    c.is_ascii() && !c.is_ascii_control()
-}
-
 #[inline]
 fn format_octal(byte: u8, buf: &mut [u8; 3]) -> &str {
     *buf = [b' ', b' ', b'0'];
@@ -518,35 +688,6 @@ fn format_octal(byte: u8, buf: &mut [u8; 3]) -> &str {
     unsafe { std::str::from_utf8_unchecked(&buf[..]) }
 }
 
-#[inline]
-fn format_byte(byte: u8) -> String {
-    let mut byte = byte;
-    let mut quoted = vec![];
-
-    if !is_ascii_printable(byte) {
-        if byte >= 128 {
-            quoted.push(b'M');
-            quoted.push(b'-');
-            byte -= 128;
-        }
-
-        if byte < 32 {
-            quoted.push(b'^');
-            byte += 64;
-        } else if byte == 127 {
-            quoted.push(b'^');
-            byte = b'?';
-        }
-        assert!((byte as char).is_ascii());
-    }
-
-    quoted.push(byte);
-
-    // SAFETY: the checks and shifts we do above match what cat and GNU
-    // cmp do to ensure characters fall inside the ascii range.
-    unsafe { String::from_utf8_unchecked(quoted) }
-}
-
 // This function has been optimized to not use the Rust fmt system, which
 // leads to a massive speed up when processing large files: cuts the time
 // for comparing 2 ~36MB completely different files in half on an M1 Max.
@@ -582,10 +723,8 @@ fn format_verbose_difference(
 
         output.push(b' ');
 
-        let from_byte_str = format_byte(from_byte);
-        let from_byte_padding = 4 - from_byte_str.len();
-
-        output.extend_from_slice(from_byte_str.as_bytes());
+        let from_byte_width = write_visible(output, from_byte).expect("write to Vec is infallible");
+        let from_byte_padding = 4 - from_byte_width;
 
         for _ in 0..from_byte_padding {
             output.push(b' ')
@@ -597,13 +736,13 @@ fn format_verbose_difference(
 
         output.push(b' ');
 
-        output.extend_from_slice(format_byte(to_byte).as_bytes());
+        write_visible(output, to_byte).expect("write to Vec is infallible");
 
         output.push(b'\n');
     } else {
         // "{:>width$} {:>3o} {:>3o}"
         let at_byte_str = at_byte_buf.format(at_byte);
-        let at_byte_padding = offset_width - at_byte_str.len();
+        let at_byte_padding = offset_width.saturating_sub(at_byte_str.len());
 
         for _ in 0..at_byte_padding {
             output.push(b' ')
@@ -663,6 +802,23 @@ fn report_eof(at_byte: usize, at_line: usize, start_of_line: bool, eof_on: &str,
     }
 }
 
+// Print the --total trailer once the comparison stopped before byte `at_byte`.
+// The listing is flushed first so that the trailer comes after it on a terminal.
+fn report_total<W: Write>(stdout: &mut W, at_byte: usize, differing: usize, params: &Params) {
+    if params.quiet || !params.total {
+        return;
+    }
+
+    let compared = params
+        .max_bytes
+        .map_or(at_byte - 1, |max_bytes| cmp::min(at_byte - 1, max_bytes));
+    let _ = stdout.flush();
+    eprintln!(
+        "{}: {compared} bytes compared, {differing} differing",
+        params.executable.to_string_lossy()
+    );
+}
+
 fn is_posix_locale() -> bool {
     let locale = if let Ok(locale) = env::var("LC_ALL") {
         locale
@@ -700,9 +856,9 @@ fn report_difference(from_byte: u8, to_byte: u8, at_byte: usize, at_line: usize,
         print!(
             " is {:>3o} {:char_width$} {:>3o} {:char_width$}",
             from_byte,
-            format_byte(from_byte),
+            ByteDisplay::new(from_byte),
             to_byte,
-            format_byte(to_byte)
+            ByteDisplay::new(to_byte)
         );
     }
     println!();
@@ -788,7 +944,7 @@ mod tests {
 
         // Bad positional arguments.
         assert_eq!(
-            Err("Usage: cmp <from> <to>".to_string()),
+            Err("Usage: cmp [OPTION]... FILE1 [FILE2 [SKIP1 [SKIP2]]]".to_string()),
             parse_params(
                 [os("cmp"), os("foo"), os("bar"), os("1"), os("2"), os("3")]
                     .iter()
@@ -797,7 +953,7 @@ mod tests {
             )
         );
         assert_eq!(
-            Err("Usage: cmp <from> <to>".to_string()),
+            Err("Usage: cmp [OPTION]... FILE1 [FILE2 [SKIP1 [SKIP2]]]".to_string()),
             parse_params([os("cmp")].iter().cloned().peekable())
         );
     }
@@ -920,6 +1076,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn total() {
+        let total = Params {
+            executable: os("cmp"),
+            from: os("foo"),
+            to: os("bar"),
+            verbose: true,
+            total: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Ok(total.clone()),
+            parse_params(
+                [os("cmp"), os("-l"), os("-T"), os("foo"), os("bar")]
+                    .iter()
+                    .cloned()
+                    .peekable()
+            )
+        );
+        assert_eq!(
+            Ok(total),
+            parse_params(
+                [os("cmp"), os("-l"), os("--total"), os("foo"), os("bar")]
+                    .iter()
+                    .cloned()
+                    .peekable()
+            )
+        );
+    }
+
+    #[test]
+    fn strip_trailing_cr_reader() {
+        let input = b"a\r\nb\r\r\n\rc\r\n\r\r";
+        for capacity in 1..=input.len() {
+            let mut reader = StripCrReader::new(BufReader::with_capacity(capacity, &input[..]));
+            let mut output = Vec::new();
+            reader.read_to_end(&mut output).unwrap();
+            assert_eq!(output, b"a\nb\r\n\rc\n\r\r", "capacity {capacity}");
+        }
+    }
+
     #[test]
     fn max_bytes() {
         let max_bytes = Params {
@@ -969,16 +1166,89 @@ mod tests {
             )
         );
 
-        // Failure case
+        // Size suffixes, as for --ignore-initial
+        assert_eq!(
+            Ok(Params {
+                executable: os("cmp"),
+                from: os("foo"),
+                to: os("bar"),
+                max_bytes: Some(1024),
+                ..Default::default()
+            }),
+            parse_params(
+                [os("cmp"), os("-n"), os("1K"), os("foo"), os("bar")]
+                    .iter()
+                    .cloned()
+                    .peekable()
+            )
+        );
+        assert_eq!(
+            Ok(Params {
+                executable: os("cmp"),
+                from: os("foo"),
+                to: os("bar"),
+                max_bytes: Some(2_000_000),
+                ..Default::default()
+            }),
+            parse_params(
+                [os("cmp"), os("--bytes=2MB"), os("foo"), os("bar")]
+                    .iter()
+                    .cloned()
+                    .peekable()
+            )
+        );
+
+        // Failure cases
         assert_eq!(
-            Err("cmp: invalid --bytes value '1K'".to_string()),
+            Err("cmp: invalid --bytes value '5Q'".to_string()),
             parse_params(
-                [os("cmp"), os("--bytes=1K"), os("foo"), os("bar")]
+                [os("cmp"), os("-n"), os("5Q"), os("foo"), os("bar")]
                     .iter()
                     .cloned()
                     .peekable()
             )
         );
+        assert_eq!(
+            Err("cmp: invalid --bytes value 'x'".to_string()),
+            parse_params(
+                [os("cmp"), os("--bytes=x"), os("foo"), os("bar")]
+                    .iter()
+                    .cloned()
+                    .peekable()
+            )
+        );
+    }
+
+    #[test]
+    fn prepare_reader_skips() {
+        use std::io::Write;
+
+        let mut temp = tempfile::NamedTempFile::new().unwrap();
+        temp.write_all(b"0123456789").unwrap();
+        let path = temp.path().as_os_str().to_os_string();
+        let params = Params::default();
+
+        let mut reader = prepare_reader(&path, &Some(4), &params).unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"456789");
+
+        // Skipping past the end leaves nothing to read
+        let mut reader = prepare_reader(&path, &Some(20), &params).unwrap();
+        assert!(reader.fill_buf().unwrap().is_empty());
+
+        // A large skip into a sparse file seeks rather than reading
+        let file = temp.as_file();
+        file.set_len(1 << 30).unwrap();
+        let mut at = file;
+        at.seek(SeekFrom::Start((1 << 30) - 4)).unwrap();
+        at.write_all(b"tail").unwrap();
+        let start = std::time::Instant::now();
+        let mut reader = prepare_reader(&path, &Some((1 << 30) - 4), &params).unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"tail");
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
     }
 
     #[test]
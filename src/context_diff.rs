@@ -4,33 +4,37 @@
 // files that was distributed with this source code.
 
 use std::collections::VecDeque;
-use std::io::Write;
+use std::io::{self, Write};
 
 use crate::params::Params;
+use crate::utils::diff_lines;
 use crate::utils::do_write_line;
-use crate::utils::get_modification_time;
+use crate::utils::find_function_context;
+use crate::utils::set_color;
+use crate::utils::write_line_marker;
+use crate::utils::{from_file_header, to_file_header};
 
 #[derive(Debug, PartialEq)]
-pub enum DiffLine {
-    Context(Vec<u8>),
-    Change(Vec<u8>),
-    Add(Vec<u8>),
+pub enum DiffLine<'a> {
+    Context(&'a [u8]),
+    Change(&'a [u8]),
+    Add(&'a [u8]),
 }
 
 #[derive(Debug, PartialEq)]
-struct Mismatch {
+struct Mismatch<'a> {
     pub line_number_expected: usize,
     pub line_number_actual: usize,
-    pub expected: Vec<DiffLine>,
-    pub actual: Vec<DiffLine>,
+    pub expected: Vec<DiffLine<'a>>,
+    pub actual: Vec<DiffLine<'a>>,
     pub expected_missing_nl: bool,
     pub actual_missing_nl: bool,
     pub expected_all_context: bool,
     pub actual_all_context: bool,
 }
 
-impl Mismatch {
-    fn new(line_number_expected: usize, line_number_actual: usize) -> Mismatch {
+impl<'a> Mismatch<'a> {
+    fn new(line_number_expected: usize, line_number_actual: usize) -> Mismatch<'a> {
         Mismatch {
             line_number_expected,
             line_number_actual,
@@ -45,16 +49,19 @@ impl Mismatch {
 }
 
 // Produces a diff between the expected output and actual output.
-fn make_diff(
-    expected: &[u8],
-    actual: &[u8],
+fn make_diff<'a>(
+    expected: &'a [u8],
+    actual: &'a [u8],
     context_size: usize,
     stop_early: bool,
-) -> Vec<Mismatch> {
+    params: &Params,
+) -> Vec<Mismatch<'a>> {
     let mut line_number_expected = 1;
     let mut line_number_actual = 1;
-    let mut context_queue: VecDeque<&[u8]> = VecDeque::with_capacity(context_size);
-    let mut lines_since_mismatch = context_size + 1;
+    let mut context_queue: VecDeque<(&[u8], &[u8])> = VecDeque::with_capacity(context_size);
+    // As in GNU diff, changes at most 2 * context_size unchanged lines apart
+    // share a hunk, so that the context of one runs into that of the other.
+    let mut lines_since_mismatch = 2 * context_size + 1;
     let mut results = Vec::new();
     let mut mismatch = Mismatch::new(0, 0);
 
@@ -74,13 +81,10 @@ fn make_diff(
         actual_lines.pop();
     }
 
-    // Rust only allows allocations to grow to isize::MAX, and this is bigger than that.
-    let mut expected_lines_change_idx: usize = !0;
-
-    for result in diff::slice(&expected_lines, &actual_lines) {
+    for result in diff_lines(&expected_lines, &actual_lines, params) {
         match result {
             diff::Result::Left(str) => {
-                if lines_since_mismatch > context_size && lines_since_mismatch > 0 {
+                if lines_since_mismatch > 2 * context_size {
                     results.push(mismatch);
                     mismatch = Mismatch::new(
                         line_number_expected - context_queue.len(),
@@ -89,12 +93,11 @@ fn make_diff(
                 }
 
                 while let Some(line) = context_queue.pop_front() {
-                    mismatch.expected.push(DiffLine::Context(line.to_vec()));
-                    mismatch.actual.push(DiffLine::Context(line.to_vec()));
+                    mismatch.expected.push(DiffLine::Context(line.0));
+                    mismatch.actual.push(DiffLine::Context(line.1));
                 }
 
-                expected_lines_change_idx = mismatch.expected.len();
-                mismatch.expected.push(DiffLine::Add(str.to_vec()));
+                mismatch.expected.push(DiffLine::Add(str));
                 if line_number_expected > expected_lines_count {
                     mismatch.expected_missing_nl = true;
                 }
@@ -102,57 +105,44 @@ fn make_diff(
                 lines_since_mismatch = 0;
             }
             diff::Result::Right(str) => {
-                if lines_since_mismatch > context_size && lines_since_mismatch > 0 {
+                if lines_since_mismatch > 2 * context_size {
                     results.push(mismatch);
                     mismatch = Mismatch::new(
                         line_number_expected - context_queue.len(),
                         line_number_actual - context_queue.len(),
                     );
-                    expected_lines_change_idx = !0;
                 }
 
                 while let Some(line) = context_queue.pop_front() {
-                    mismatch.expected.push(DiffLine::Context(line.to_vec()));
-                    mismatch.actual.push(DiffLine::Context(line.to_vec()));
+                    mismatch.expected.push(DiffLine::Context(line.0));
+                    mismatch.actual.push(DiffLine::Context(line.1));
                 }
 
-                if let Some(DiffLine::Add(content)) =
-                    mismatch.expected.get_mut(expected_lines_change_idx)
-                {
-                    let content = std::mem::take(content);
-                    mismatch.expected[expected_lines_change_idx] = DiffLine::Change(content);
-                    expected_lines_change_idx = expected_lines_change_idx.wrapping_sub(1); // if 0, becomes !0
-                    mismatch.actual.push(DiffLine::Change(str.to_vec()));
-                } else {
-                    mismatch.actual.push(DiffLine::Add(str.to_vec()));
-                }
+                mismatch.actual.push(DiffLine::Add(str));
                 if line_number_actual > actual_lines_count {
                     mismatch.actual_missing_nl = true;
                 }
                 line_number_actual += 1;
                 lines_since_mismatch = 0;
             }
-            diff::Result::Both(str, _) => {
-                expected_lines_change_idx = !0;
-                // if one of them is missing a newline and the other isn't, then they don't actually match
-                if (line_number_actual > actual_lines_count)
-                    && (line_number_expected > expected_lines_count)
+            diff::Result::Both(left, right) => {
+                let expected_missing_nl = line_number_expected > expected_lines_count;
+                let actual_missing_nl = line_number_actual > actual_lines_count;
+                // if one of them is missing a newline and the other isn't, then they don't actually match,
+                // unless -Z counts the newline as trailing white space
+                if expected_missing_nl && actual_missing_nl
+                    || params.ignore_trailing_space && (expected_missing_nl || actual_missing_nl)
                 {
-                    if context_queue.len() < context_size {
-                        while let Some(line) = context_queue.pop_front() {
-                            mismatch.expected.push(DiffLine::Context(line.to_vec()));
-                            mismatch.actual.push(DiffLine::Context(line.to_vec()));
-                        }
-                        if lines_since_mismatch < context_size {
-                            mismatch.expected.push(DiffLine::Context(str.to_vec()));
-                            mismatch.actual.push(DiffLine::Context(str.to_vec()));
-                            mismatch.expected_missing_nl = true;
-                            mismatch.actual_missing_nl = true;
-                        }
+                    // The last line: part of the trailing context, if any
+                    if lines_since_mismatch < context_size {
+                        mismatch.expected.push(DiffLine::Context(left));
+                        mismatch.actual.push(DiffLine::Context(right));
+                        mismatch.expected_missing_nl = expected_missing_nl;
+                        mismatch.actual_missing_nl = actual_missing_nl;
                     }
-                    lines_since_mismatch = 0;
-                } else if line_number_actual > actual_lines_count {
-                    if lines_since_mismatch >= context_size && lines_since_mismatch > 0 {
+                    lines_since_mismatch += 1;
+                } else if actual_missing_nl {
+                    if lines_since_mismatch > 2 * context_size {
                         results.push(mismatch);
                         mismatch = Mismatch::new(
                             line_number_expected - context_queue.len(),
@@ -160,15 +150,15 @@ fn make_diff(
                         );
                     }
                     while let Some(line) = context_queue.pop_front() {
-                        mismatch.expected.push(DiffLine::Context(line.to_vec()));
-                        mismatch.actual.push(DiffLine::Context(line.to_vec()));
+                        mismatch.expected.push(DiffLine::Context(line.0));
+                        mismatch.actual.push(DiffLine::Context(line.1));
                     }
-                    mismatch.expected.push(DiffLine::Change(str.to_vec()));
-                    mismatch.actual.push(DiffLine::Change(str.to_vec()));
+                    mismatch.expected.push(DiffLine::Change(left));
+                    mismatch.actual.push(DiffLine::Change(right));
                     mismatch.actual_missing_nl = true;
                     lines_since_mismatch = 0;
-                } else if line_number_expected > expected_lines_count {
-                    if lines_since_mismatch >= context_size && lines_since_mismatch > 0 {
+                } else if expected_missing_nl {
+                    if lines_since_mismatch > 2 * context_size {
                         results.push(mismatch);
                         mismatch = Mismatch::new(
                             line_number_expected - context_queue.len(),
@@ -176,12 +166,12 @@ fn make_diff(
                         );
                     }
                     while let Some(line) = context_queue.pop_front() {
-                        mismatch.expected.push(DiffLine::Context(line.to_vec()));
-                        mismatch.actual.push(DiffLine::Context(line.to_vec()));
+                        mismatch.expected.push(DiffLine::Context(line.0));
+                        mismatch.actual.push(DiffLine::Context(line.1));
                     }
-                    mismatch.expected.push(DiffLine::Change(str.to_vec()));
+                    mismatch.expected.push(DiffLine::Change(left));
                     mismatch.expected_missing_nl = true;
-                    mismatch.actual.push(DiffLine::Change(str.to_vec()));
+                    mismatch.actual.push(DiffLine::Change(right));
                     lines_since_mismatch = 0;
                 } else {
                     debug_assert!(context_queue.len() <= context_size);
@@ -189,10 +179,10 @@ fn make_diff(
                         let _ = context_queue.pop_front();
                     }
                     if lines_since_mismatch < context_size {
-                        mismatch.expected.push(DiffLine::Context(str.to_vec()));
-                        mismatch.actual.push(DiffLine::Context(str.to_vec()));
+                        mismatch.expected.push(DiffLine::Context(left));
+                        mismatch.actual.push(DiffLine::Context(right));
                     } else if context_size > 0 {
-                        context_queue.push_back(str);
+                        context_queue.push_back((left, right));
                     }
                     lines_since_mismatch += 1;
                 }
@@ -209,36 +199,35 @@ fn make_diff(
     results.push(mismatch);
     results.remove(0);
 
-    if results.is_empty() && expected_lines_count != actual_lines_count {
+    if results.is_empty()
+        && expected_lines_count != actual_lines_count
+        && !params.ignore_trailing_space
+    {
         let mut mismatch = Mismatch::new(expected_lines.len(), actual_lines.len());
         // empty diff and only expected lines has a missing line at end
         if expected_lines_count != expected_lines.len() {
             mismatch.expected.push(DiffLine::Change(
                 expected_lines
                     .pop()
-                    .expect("can't be empty; produced by split()")
-                    .to_vec(),
+                    .expect("can't be empty; produced by split()"),
             ));
             mismatch.expected_missing_nl = true;
             mismatch.actual.push(DiffLine::Change(
                 actual_lines
                     .pop()
-                    .expect("can't be empty; produced by split()")
-                    .to_vec(),
+                    .expect("can't be empty; produced by split()"),
             ));
             results.push(mismatch);
         } else if actual_lines_count != actual_lines.len() {
             mismatch.expected.push(DiffLine::Change(
                 expected_lines
                     .pop()
-                    .expect("can't be empty; produced by split()")
-                    .to_vec(),
+                    .expect("can't be empty; produced by split()"),
             ));
             mismatch.actual.push(DiffLine::Change(
                 actual_lines
                     .pop()
-                    .expect("can't be empty; produced by split()")
-                    .to_vec(),
+                    .expect("can't be empty; produced by split()"),
             ));
             mismatch.actual_missing_nl = true;
             results.push(mismatch);
@@ -247,6 +236,7 @@ fn make_diff(
 
     // hunks with pure context lines get truncated to empty
     for mismatch in &mut results {
+        mark_changes(mismatch);
         if !mismatch
             .expected
             .iter()
@@ -266,25 +256,78 @@ fn make_diff(
     results
 }
 
+// As in GNU diff, every line of a group of changes that both removes and adds
+// lines is marked as changed, and the other groups as plain removals or
+// additions. Context lines come in pairs, so the nth group of one side faces
+// the nth group of the other.
+fn mark_changes(mismatch: &mut Mismatch) {
+    let group_end = |lines: &[DiffLine], start: usize| {
+        lines[start..]
+            .iter()
+            .position(|line| matches!(line, DiffLine::Context(_)))
+            .map_or(lines.len(), |pos| start + pos)
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < mismatch.expected.len() || j < mismatch.actual.len() {
+        let (end_i, end_j) = (
+            group_end(&mismatch.expected, i),
+            group_end(&mismatch.actual, j),
+        );
+        if end_i > i && end_j > j {
+            for line in mismatch.expected[i..end_i]
+                .iter_mut()
+                .chain(&mut mismatch.actual[j..end_j])
+            {
+                if let DiffLine::Add(content) = *line {
+                    *line = DiffLine::Change(content);
+                }
+            }
+        }
+        (i, j) = (end_i + 1, end_j + 1);
+    }
+}
+
 #[must_use]
 pub fn diff(expected: &[u8], actual: &[u8], params: &Params) -> Vec<u8> {
-    let from_modified_time = get_modification_time(&params.from.to_string_lossy());
-    let to_modified_time = get_modification_time(&params.to.to_string_lossy());
-    let mut output = format!(
-        "*** {0}\t{1}\n--- {2}\t{3}\n",
-        params.from.to_string_lossy(),
-        from_modified_time,
-        params.to.to_string_lossy(),
-        to_modified_time
-    )
-    .into_bytes();
-    let diff_results = make_diff(expected, actual, params.context_count, params.brief);
+    let mut output = Vec::new();
+    diff_to_writer(expected, actual, params, &mut output).expect("write to Vec is infallible");
+    output
+}
+
+/// Like [`diff`], but write each hunk to `output` as soon as it is formatted
+/// rather than collecting the whole diff in memory. Returns whether the
+/// inputs differ.
+pub fn diff_to_writer<W: Write>(
+    expected: &[u8],
+    actual: &[u8],
+    params: &Params,
+    output: &mut W,
+) -> io::Result<bool> {
+    let diff_results = make_diff(expected, actual, params.context_count, params.brief, params);
     if diff_results.is_empty() {
-        return Vec::new();
+        return Ok(false);
+    }
+    // Under --color, each header line is a single span, as in unified output
+    for (marker, header) in [
+        (b"*** ", from_file_header(params)),
+        (b"--- ", to_file_header(params)),
+    ] {
+        set_color(output, params.color, &params.palette.header)?;
+        output.write_all(marker)?;
+        output.write_all(&header)?;
+        set_color(output, params.color, &params.palette.reset)?;
+        writeln!(output)?;
     }
     if params.brief {
-        return output;
+        return Ok(true);
     }
+    let expected_lines: Vec<&[u8]> = if params.show_c_function {
+        expected.split(|&c| c == b'\n').collect()
+    } else {
+        Vec::new()
+    };
+    // With -T, a tab instead of a space separates the indicator from the line
+    let separator = if params.initial_tab { "\t" } else { " " };
     for result in diff_results {
         let mut line_number_expected = result.line_number_expected;
         let mut line_number_actual = result.line_number_actual;
@@ -310,71 +353,89 @@ pub fn diff(expected: &[u8], actual: &[u8], params: &Params) -> Vec<u8> {
         } else {
             format!("{line_number_actual},")
         };
-        writeln!(
-            output,
-            "***************\n*** {exp_start}{end_line_number_expected} ****"
-        )
-        .expect("write to Vec is infallible");
+        write!(output, "***************")?;
+        if params.show_c_function {
+            // Lines before the first one of the hunk, which for a pure
+            // insertion is the line line_number_expected points at.
+            let count = if result.expected.is_empty() {
+                line_number_expected
+            } else {
+                line_number_expected - 1
+            };
+            if let Some(function) = find_function_context(&expected_lines, count) {
+                write!(output, " ")?;
+                output.write_all(function)?;
+            }
+        }
+        writeln!(output)?;
+        set_color(output, params.color, &params.palette.line_number)?;
+        write!(output, "*** {exp_start}{end_line_number_expected} ****")?;
+        set_color(output, params.color, &params.palette.reset)?;
+        writeln!(output)?;
+        // Like GNU diff, --color shows every line of a section, context
+        // included, in the color of the file it comes from
         if !result.expected_all_context {
             for line in result.expected {
+                set_color(output, params.color, &params.palette.delete)?;
                 match line {
                     DiffLine::Context(e) => {
-                        write!(output, "  ").expect("write to Vec is infallible");
-                        do_write_line(&mut output, &e, params.expand_tabs, params.tabsize)
-                            .expect("write to Vec is infallible");
-                        writeln!(output).unwrap();
+                        write_line_marker(output, " ", separator, e, params)?;
+                        do_write_line(output, e, params.expand_tabs, params.tabsize)?;
+                        set_color(output, params.color, &params.palette.reset)?;
+                        writeln!(output)?;
                     }
                     DiffLine::Change(e) => {
-                        write!(output, "! ").expect("write to Vec is infallible");
-                        do_write_line(&mut output, &e, params.expand_tabs, params.tabsize)
-                            .expect("write to Vec is infallible");
-                        writeln!(output).unwrap();
+                        write_line_marker(output, "!", separator, e, params)?;
+                        do_write_line(output, e, params.expand_tabs, params.tabsize)?;
+                        set_color(output, params.color, &params.palette.reset)?;
+                        writeln!(output)?;
                     }
                     DiffLine::Add(e) => {
-                        write!(output, "- ").expect("write to Vec is infallible");
-                        do_write_line(&mut output, &e, params.expand_tabs, params.tabsize)
-                            .expect("write to Vec is infallible");
-                        writeln!(output).unwrap();
+                        write_line_marker(output, "-", separator, e, params)?;
+                        do_write_line(output, e, params.expand_tabs, params.tabsize)?;
+                        set_color(output, params.color, &params.palette.reset)?;
+                        writeln!(output)?;
                     }
                 }
             }
             if result.expected_missing_nl {
-                writeln!(output, r"\ No newline at end of file")
-                    .expect("write to Vec is infallible");
+                writeln!(output, r"\ No newline at end of file")?;
             }
         }
-        writeln!(output, "--- {act_start}{end_line_number_actual} ----")
-            .expect("write to Vec is infallible");
+        set_color(output, params.color, &params.palette.line_number)?;
+        write!(output, "--- {act_start}{end_line_number_actual} ----")?;
+        set_color(output, params.color, &params.palette.reset)?;
+        writeln!(output)?;
         if !result.actual_all_context {
             for line in result.actual {
+                set_color(output, params.color, &params.palette.add)?;
                 match line {
                     DiffLine::Context(e) => {
-                        write!(output, "  ").expect("write to Vec is infallible");
-                        do_write_line(&mut output, &e, params.expand_tabs, params.tabsize)
-                            .expect("write to Vec is infallible");
-                        writeln!(output).unwrap();
+                        write_line_marker(output, " ", separator, e, params)?;
+                        do_write_line(output, e, params.expand_tabs, params.tabsize)?;
+                        set_color(output, params.color, &params.palette.reset)?;
+                        writeln!(output)?;
                     }
                     DiffLine::Change(e) => {
-                        write!(output, "! ").expect("write to Vec is infallible");
-                        do_write_line(&mut output, &e, params.expand_tabs, params.tabsize)
-                            .expect("write to Vec is infallible");
-                        writeln!(output).unwrap();
+                        write_line_marker(output, "!", separator, e, params)?;
+                        do_write_line(output, e, params.expand_tabs, params.tabsize)?;
+                        set_color(output, params.color, &params.palette.reset)?;
+                        writeln!(output)?;
                     }
                     DiffLine::Add(e) => {
-                        write!(output, "+ ").expect("write to Vec is infallible");
-                        do_write_line(&mut output, &e, params.expand_tabs, params.tabsize)
-                            .expect("write to Vec is infallible");
-                        writeln!(output).unwrap();
+                        write_line_marker(output, "+", separator, e, params)?;
+                        do_write_line(output, e, params.expand_tabs, params.tabsize)?;
+                        set_color(output, params.color, &params.palette.reset)?;
+                        writeln!(output)?;
                     }
                 }
             }
             if result.actual_missing_nl {
-                writeln!(output, r"\ No newline at end of file")
-                    .expect("write to Vec is infallible");
+                writeln!(output, r"\ No newline at end of file")?;
             }
         }
     }
-    output
+    Ok(true)
 }
 
 #[cfg(test)]
@@ -633,6 +694,108 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_permutations_missing_line_ending() {
+        let target = "target/context-diff/";
+        // test all possible six-line files with missing newlines.
+        let _ = std::fs::create_dir(target);
+        for &a in &[0, 1, 2] {
+            for &b in &[0, 1, 2] {
+                for &c in &[0, 1, 2] {
+                    for &d in &[0, 1, 2] {
+                        for &e in &[0, 1, 2] {
+                            for &f in &[0, 1, 2] {
+                                for &g in &[0, 1, 2] {
+                                    use std::fs::{self, File};
+                                    use std::io::Write;
+                                    use std::process::Command;
+                                    let mut alef = Vec::new();
+                                    let mut bet = Vec::new();
+                                    alef.write_all(if a == 0 { b"a\n" } else { b"b\n" })
+                                        .unwrap();
+                                    if a != 2 {
+                                        bet.write_all(b"b\n").unwrap();
+                                    }
+                                    alef.write_all(if b == 0 { b"c\n" } else { b"d\n" })
+                                        .unwrap();
+                                    if b != 2 {
+                                        bet.write_all(b"d\n").unwrap();
+                                    }
+                                    alef.write_all(if c == 0 { b"e\n" } else { b"f\n" })
+                                        .unwrap();
+                                    if c != 2 {
+                                        bet.write_all(b"f\n").unwrap();
+                                    }
+                                    alef.write_all(if d == 0 { b"g\n" } else { b"h\n" })
+                                        .unwrap();
+                                    if d != 2 {
+                                        bet.write_all(b"h\n").unwrap();
+                                    }
+                                    alef.write_all(if e == 0 { b"i\n" } else { b"j\n" })
+                                        .unwrap();
+                                    if e != 2 {
+                                        bet.write_all(b"j\n").unwrap();
+                                    }
+                                    alef.write_all(if f == 0 { b"k\n" } else { b"l\n" })
+                                        .unwrap();
+                                    if f != 2 {
+                                        bet.write_all(b"l\n").unwrap();
+                                    }
+                                    match g {
+                                        0 => {
+                                            alef.pop();
+                                        }
+                                        1 => {
+                                            bet.pop();
+                                        }
+                                        2 => {
+                                            alef.pop();
+                                            bet.pop();
+                                        }
+                                        _ => unreachable!(),
+                                    }
+                                    // This test diff is intentionally reversed.
+                                    // We want it to turn the alef into bet.
+                                    let diff = diff(
+                                        &alef,
+                                        &bet,
+                                        &Params {
+                                            from: "a/alefm".into(),
+                                            to: (&format!("{target}/alefm")).into(),
+                                            context_count: 2,
+                                            ..Default::default()
+                                        },
+                                    );
+                                    File::create(format!("{target}/abm.diff"))
+                                        .unwrap()
+                                        .write_all(&diff)
+                                        .unwrap();
+                                    let mut fa = File::create(format!("{target}/alefm")).unwrap();
+                                    fa.write_all(&alef[..]).unwrap();
+                                    let mut fb = File::create(format!("{target}/betm")).unwrap();
+                                    fb.write_all(&bet[..]).unwrap();
+                                    let _ = fa;
+                                    let _ = fb;
+                                    let output = Command::new("patch")
+                                        .arg("-p0")
+                                        .arg("--context")
+                                        .stdin(File::open(format!("{target}/abm.diff")).unwrap())
+                                        .output()
+                                        .unwrap();
+                                    assert!(output.status.success(), "{output:?}");
+                                    //println!("{}", String::from_utf8_lossy(&output.stdout));
+                                    //println!("{}", String::from_utf8_lossy(&output.stderr));
+                                    let alef = fs::read(format!("{target}/alefm")).unwrap();
+                                    assert_eq!(alef, bet);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_permutations_reverse() {
         let target = "target/context-diff/";
@@ -793,4 +956,254 @@ mod tests {
         );
         assert!(nodiff_brief.is_empty());
     }
+
+    #[test]
+    fn test_initial_tab() {
+        use crate::assert_diff_eq;
+
+        let from = ["a", "b", "c"].join("\n");
+        let to = ["a", "d", "c"].join("\n");
+
+        let check = |initial_tab: bool| {
+            let diff = diff(
+                from.as_bytes(),
+                to.as_bytes(),
+                &Params {
+                    from: "foo".into(),
+                    to: "bar".into(),
+                    initial_tab,
+                    ..Default::default()
+                },
+            );
+            let sep = if initial_tab { "\t" } else { " " };
+            let expected = [
+                "*** foo\tTIMESTAMP",
+                "--- bar\tTIMESTAMP",
+                "***************",
+                "*** 1,3 ****",
+                &format!(" {sep}a"),
+                &format!("!{sep}b"),
+                &format!(" {sep}c"),
+                r"\ No newline at end of file",
+                "--- 1,3 ----",
+                &format!(" {sep}a"),
+                &format!("!{sep}d"),
+                &format!(" {sep}c"),
+                r"\ No newline at end of file",
+                "",
+            ]
+            .join("\n");
+            assert_diff_eq!(diff, expected);
+        };
+        check(false);
+        check(true);
+    }
+
+    #[test]
+    fn test_show_c_function() {
+        use crate::assert_diff_eq;
+
+        let from = [
+            "int main() {",
+            "  a();",
+            "  b();",
+            "  c();",
+            "  d();",
+            "}",
+            "",
+        ]
+        .join("\n");
+        let to = [
+            "int main() {",
+            "  a();",
+            "  b();",
+            "  c();",
+            "  e();",
+            "}",
+            "",
+        ]
+        .join("\n");
+
+        let diff = diff(
+            from.as_bytes(),
+            to.as_bytes(),
+            &Params {
+                from: "foo".into(),
+                to: "bar".into(),
+                show_c_function: true,
+                ..Default::default()
+            },
+        );
+        let expected = [
+            "*** foo\tTIMESTAMP",
+            "--- bar\tTIMESTAMP",
+            "*************** int main() {",
+            "*** 2,6 ****",
+            "    a();",
+            "    b();",
+            "    c();",
+            "!   d();",
+            "  }",
+            "--- 2,6 ----",
+            "    a();",
+            "    b();",
+            "    c();",
+            "!   e();",
+            "  }",
+            "",
+        ]
+        .join("\n");
+        assert_diff_eq!(diff, expected);
+    }
+
+    #[test]
+    fn test_change_groups() {
+        // Every line of a group that both removes and adds lines is marked
+        // with '!', however many lines each side has
+        let params = Params {
+            from: "foo".into(),
+            to: "bar".into(),
+            ..Default::default()
+        };
+        let body = |from: &[u8], to: &[u8]| -> String {
+            String::from_utf8(diff(from, to, &params))
+                .unwrap()
+                .split_inclusive('\n')
+                .skip(3)
+                .collect()
+        };
+        assert_eq!(
+            body(b"a\nc\n", b"b\n"),
+            "*** 1,2 ****\n! a\n! c\n--- 1 ----\n! b\n"
+        );
+        assert_eq!(
+            body(b"a\nj\nl\n", b"b\nj"),
+            "*** 1,3 ****\n! a\n! j\n! l\n--- 1,2 ----\n! b\n! j\n\\ No newline at end of file\n"
+        );
+        assert_eq!(
+            body(b"j", b"j\nl\n"),
+            "*** 1 ****\n! j\n\\ No newline at end of file\n--- 1,2 ----\n! j\n! l\n"
+        );
+        // Groups separated by context are marked on their own
+        assert_eq!(
+            body(b"a\nb\nc\nd\n", b"x\nb\nd\ne\n"),
+            "*** 1,4 ****\n! a\n  b\n- c\n  d\n--- 1,4 ----\n! x\n  b\n  d\n+ e\n"
+        );
+    }
+
+    #[test]
+    fn test_color() {
+        use crate::assert_diff_eq;
+
+        // As in GNU diff, context lines take the color of their section too
+        let params = Params {
+            from: "foo".into(),
+            to: "bar".into(),
+            color: true,
+            ..Default::default()
+        };
+        let diff = diff(b"a\nb\nc\n", b"a\nd\nc\ne\n", &params);
+        let expected = [
+            "\x1b[1m*** foo\tTIMESTAMP\x1b[0m",
+            "\x1b[1m--- bar\tTIMESTAMP\x1b[0m",
+            "***************",
+            "\x1b[36m*** 1,3 ****\x1b[0m",
+            "\x1b[31m  a\x1b[0m",
+            "\x1b[31m! b\x1b[0m",
+            "\x1b[31m  c\x1b[0m",
+            "\x1b[36m--- 1,4 ----\x1b[0m",
+            "\x1b[32m  a\x1b[0m",
+            "\x1b[32m! d\x1b[0m",
+            "\x1b[32m  c\x1b[0m",
+            "\x1b[32m+ e\x1b[0m",
+            "",
+        ]
+        .join("\n");
+        assert_diff_eq!(diff, expected);
+    }
+
+    #[test]
+    fn test_headers() {
+        use crate::assert_diff_eq;
+        use crate::utils::get_modification_time;
+
+        let from = b"a\nb\n";
+        let to = b"a\nc\n";
+        let header = |output: &[u8], lines: usize| -> String {
+            String::from_utf8_lossy(output)
+                .split_inclusive('\n')
+                .take(lines)
+                .collect()
+        };
+
+        // Same names and timestamps as the unified format, with its markers
+        let params = Params {
+            from: "Cargo.toml".into(),
+            to: "README.md".into(),
+            ..Default::default()
+        };
+        let expected = format!(
+            "*** Cargo.toml\t{}\n--- README.md\t{}\n",
+            get_modification_time("Cargo.toml"),
+            get_modification_time("README.md")
+        );
+        assert_eq!(header(&diff(from, to, &params), 2), expected);
+        let unified = crate::unified_diff::diff(from, to, &params);
+        assert_eq!(
+            header(&unified, 2),
+            expected
+                .replacen("--- ", "+++ ", 1)
+                .replacen("*** ", "--- ", 1)
+        );
+
+        // Timestamps are normalized by assert_diff_eq!, including for stdin
+        let params = Params {
+            from: "-".into(),
+            to: "bar".into(),
+            brief: true,
+            ..Default::default()
+        };
+        let output = diff(from, to, &params);
+        assert_diff_eq!(output, "*** -\tTIMESTAMP\n--- bar\tTIMESTAMP\n");
+
+        // Labels replace both the name and the timestamp
+        let params = Params {
+            from: "foo".into(),
+            to: "bar".into(),
+            from_label: Some("a".into()),
+            to_label: Some("b".into()),
+            brief: true,
+            ..Default::default()
+        };
+        assert_eq!(diff(from, to, &params), b"*** a\n--- b\n");
+    }
+
+    #[test]
+    fn test_diff_to_writer() {
+        let from = b"a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let to = b"a\nB\nc\nd\ne\nf\ng\nh\nI\nj";
+        // Name an existing file so that both runs print the same timestamp
+        for params in [
+            Params {
+                from: "Cargo.toml".into(),
+                to: "Cargo.toml".into(),
+                ..Default::default()
+            },
+            Params {
+                from: "Cargo.toml".into(),
+                to: "Cargo.toml".into(),
+                context_count: 1,
+                brief: true,
+                ..Default::default()
+            },
+        ] {
+            let mut output = Vec::new();
+            assert!(diff_to_writer(from, to, &params, &mut output).unwrap());
+            assert_eq!(output, diff(from, to, &params));
+
+            let mut output = Vec::new();
+            assert!(!diff_to_writer(from, from, &params, &mut output).unwrap());
+            assert!(output.is_empty());
+        }
+    }
 }
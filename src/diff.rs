@@ -3,15 +3,19 @@
 // For the full copyright and license information, please view the LICENSE-*
 // files that was distributed with this source code.
 
-use crate::params::{parse_params, Format};
-use crate::utils::report_failure_to_read_input_file;
-use crate::{context_diff, ed_diff, normal_diff, unified_diff};
-use std::env::ArgsOs;
-use std::ffi::OsString;
-use std::fs;
-use std::io::{self, Read, Write};
+use crate::params::{parse_params, Format, Params};
+use crate::utils::{
+    format_io_error, is_binary, prepare_input, read_file_contents,
+    report_failure_to_read_input_file, FileContents,
+};
+use crate::{context_diff, ed_diff, json_diff, normal_diff, rcs_diff, unified_diff};
+use std::env::{self, ArgsOs};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, BufWriter, ErrorKind, Write};
 use std::iter::Peekable;
-use std::process::{exit, ExitCode};
+use std::path::Path;
+use std::process::{exit, ChildStdin, Command, ExitCode, Stdio};
 
 // Exit codes are documented at
 // https://www.gnu.org/software/diffutils/manual/html_node/Invoking-diff.html.
@@ -23,39 +27,63 @@ pub fn main(opts: Peekable<ArgsOs>) -> ExitCode {
         eprintln!("{error}");
         exit(2);
     });
-    // if from and to are the same file, no need to perform any comparison
-    let maybe_report_identical_files = || {
-        if params.report_identical_files {
-            println!(
+    let mut output = open_output(&params).unwrap_or_else(|error| {
+        eprintln!("{}: {error}", params.executable.to_string_lossy());
+        exit(2);
+    });
+    let report_identical_files = |output: &mut dyn Write| {
+        if params.report_identical_files && !params.check {
+            writeln!(
+                output,
                 "Files {} and {} are identical",
                 params.from.to_string_lossy(),
                 params.to.to_string_lossy(),
-            );
+            )?;
         }
+        Ok(())
     };
+    // if from and to are the same file, no need to perform any comparison
+    // "-" is always stdin, even when a file named "-" exists
     if params.from == "-" && params.to == "-"
-        || same_file::is_same_file(&params.from, &params.to).unwrap_or(false)
+        || params.from != "-"
+            && params.to != "-"
+            && same_file::is_same_file(&params.from, &params.to).unwrap_or(false)
     {
-        maybe_report_identical_files();
-        return ExitCode::SUCCESS;
+        // The JSON format still shows an array, with no hunks in it
+        let result = if params.format == Format::Json && !params.brief && !params.check {
+            json_diff::diff_to_writer(b"", b"", &params, &mut output).map(|_| ())
+        } else {
+            Ok(())
+        };
+        return match result
+            .and_then(|()| report_identical_files(&mut output))
+            .and_then(|()| output.flush())
+        {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => {
+                eprintln!("{}: {error}", params.executable.to_string_lossy());
+                ExitCode::from(2)
+            }
+        };
     }
 
     // read files
-    fn read_file_contents(filepath: &OsString) -> io::Result<Vec<u8>> {
-        if filepath == "-" {
-            let mut content = Vec::new();
-            io::stdin().read_to_end(&mut content).and(Ok(content))
-        } else {
-            fs::read(filepath)
-        }
-    }
     let mut io_error = false;
     let from_content = match read_file_contents(&params.from) {
         Ok(from_content) => from_content,
+        // Like GNU diff, only an absent first file is treated as empty, and
+        // only when there is a second file to compare it with; stdin counts.
+        Err(e)
+            if e.kind() == ErrorKind::NotFound
+                && params.unidirectional_new_file
+                && (params.to == "-" || Path::new(&params.to).exists()) =>
+        {
+            FileContents::Read(vec![])
+        }
         Err(e) => {
             report_failure_to_read_input_file(&params.executable, &params.from, &e);
             io_error = true;
-            vec![]
+            FileContents::Read(vec![])
         }
     };
     let to_content = match read_file_contents(&params.to) {
@@ -63,36 +91,207 @@ pub fn main(opts: Peekable<ArgsOs>) -> ExitCode {
         Err(e) => {
             report_failure_to_read_input_file(&params.executable, &params.to, &e);
             io_error = true;
-            vec![]
+            FileContents::Read(vec![])
         }
     };
     if io_error {
         return ExitCode::from(2);
     }
+    let from_content = prepare_input(&from_content, &params);
+    let to_content = prepare_input(&to_content, &params);
+
+    // Like GNU diff, only report whether binary inputs differ, unless -a.
+    // Identical ones still give the empty array of the JSON format.
+    let binary = !params.text
+        && (is_binary(&from_content) || is_binary(&to_content))
+        && !(params.format == Format::Json && from_content == to_content);
 
     // run diff
-    let result: Vec<u8> = match params.format {
-        Format::Normal => normal_diff::diff(&from_content, &to_content, &params),
-        Format::Unified => unified_diff::diff(&from_content, &to_content, &params),
-        Format::Context => context_diff::diff(&from_content, &to_content, &params),
-        Format::Ed => ed_diff::diff(&from_content, &to_content, &params).unwrap_or_else(|error| {
+    let result = if binary {
+        Ok(from_content[..] != to_content[..])
+    } else if params.brief || params.check {
+        // Whether the files differ does not depend on the output format, and
+        // unlike ed's, the normal format copes with a missing final newline.
+        Ok(!normal_diff::diff(&from_content, &to_content, &params).is_empty())
+    } else if params.paginate {
+        paginate(&params, |output| {
+            write_diff(&from_content, &to_content, &params, output)
+        })
+    } else {
+        write_diff(&from_content, &to_content, &params, &mut output)
+    };
+    let result = result.and_then(|differs| {
+        if params.check {
+            // Only the exit status tells
+        } else if params.brief && differs {
+            writeln!(
+                output,
+                "Files {} and {} differ",
+                params.from.to_string_lossy(),
+                params.to.to_string_lossy()
+            )?;
+        } else if binary && differs {
+            writeln!(
+                output,
+                "Binary files {} and {} differ",
+                params.from.to_string_lossy(),
+                params.to.to_string_lossy()
+            )?;
+        } else if !differs {
+            report_identical_files(&mut output)?;
+        }
+        output.flush()?;
+        Ok(differs)
+    });
+    match result {
+        Ok(true) => ExitCode::from(1),
+        Ok(false) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}: {error}", params.executable.to_string_lossy());
+            ExitCode::from(2)
+        }
+    }
+}
+
+// Where the diff goes: the file given with --output, or stdout. The output
+// file is refused if it is one of the inputs, which creating it would
+// truncate before it is read.
+fn open_output(params: &Params) -> io::Result<Box<dyn Write + '_>> {
+    let Some(path) = params.output.as_ref().filter(|path| *path != "-") else {
+        return Ok(Box::new(BufWriter::new(io::stdout().lock())));
+    };
+    for input in [&params.from, &params.to] {
+        if input != "-" && same_file::is_same_file(path, input).unwrap_or(false) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{}: output file is also an input file",
+                    path.to_string_lossy()
+                ),
+            ));
+        }
+    }
+    let file = File::create(path).map_err(|error| with_path(path, error))?;
+    Ok(Box::new(OutputFile {
+        file: BufWriter::new(file),
+        path,
+    }))
+}
+
+// Name the --output file in an error, shown like input file errors are.
+fn with_path(path: &OsStr, error: io::Error) -> io::Error {
+    io::Error::new(
+        error.kind(),
+        format!("{}: {}", path.to_string_lossy(), format_io_error(&error)),
+    )
+}
+
+// The --output file, whose write errors name it, as those of stdout need not.
+struct OutputFile<'a> {
+    file: BufWriter<File>,
+    path: &'a OsStr,
+}
+
+impl Write for OutputFile<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file
+            .write(buf)
+            .map_err(|error| with_path(self.path, error))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file
+            .flush()
+            .map_err(|error| with_path(self.path, error))
+    }
+}
+
+// Produce the whole diff in the requested format at once.
+fn buffered_diff(from_content: &[u8], to_content: &[u8], params: &Params) -> Vec<u8> {
+    match params.format {
+        Format::Normal => normal_diff::diff(from_content, to_content, params),
+        Format::Unified => unified_diff::diff(from_content, to_content, params),
+        Format::Context => context_diff::diff(from_content, to_content, params),
+        Format::Json => json_diff::diff(from_content, to_content, params),
+        Format::Rcs => rcs_diff::diff(from_content, to_content, params),
+        Format::Ed => ed_diff::diff(from_content, to_content, params).unwrap_or_else(|error| {
             eprintln!("{error}");
             exit(2);
         }),
-    };
-    if params.brief && !result.is_empty() {
-        println!(
-            "Files {} and {} differ",
-            params.from.to_string_lossy(),
-            params.to.to_string_lossy()
-        );
-    } else {
-        io::stdout().write_all(&result).unwrap();
     }
-    if result.is_empty() {
-        maybe_report_identical_files();
-        ExitCode::SUCCESS
-    } else {
-        ExitCode::from(1)
+}
+
+// Write the diff in the requested format to output, returning whether the
+// files differ. The RCS and ed scripts are buffered first; the other formats
+// are written hunk by hunk.
+fn write_diff<W: Write>(
+    from_content: &[u8],
+    to_content: &[u8],
+    params: &Params,
+    output: &mut W,
+) -> io::Result<bool> {
+    match params.format {
+        Format::Normal => normal_diff::diff_to_writer(from_content, to_content, params, output),
+        Format::Unified => unified_diff::diff_to_writer(from_content, to_content, params, output),
+        Format::Context => context_diff::diff_to_writer(from_content, to_content, params, output),
+        Format::Json => json_diff::diff_to_writer(from_content, to_content, params, output),
+        Format::Rcs | Format::Ed => {
+            let script = buffered_diff(from_content, to_content, params);
+            output.write_all(&script)?;
+            // A script has no header, so it is empty exactly when the files
+            // are the same
+            Ok(!script.is_empty())
+        }
+    }
+}
+
+// Pass the output through `pr`, or through the program given with --pager
+// or the PR environment variable. Only the default `pr` gets a page header,
+// as an arbitrary program may not understand the -h option.
+fn paginate<F>(params: &Params, write: F) -> io::Result<bool>
+where
+    F: FnOnce(&mut BufWriter<ChildStdin>) -> io::Result<bool>,
+{
+    let mut command = match params.pager.clone().or_else(|| env::var_os("PR")) {
+        Some(pager) => Command::new(pager),
+        None => {
+            let mut command = Command::new("pr");
+            command.arg("-h").arg(format!(
+                "{} {} {}",
+                params.executable.to_string_lossy(),
+                params.from.to_string_lossy(),
+                params.to.to_string_lossy()
+            ));
+            command
+        }
+    };
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command.stdin(Stdio::piped()).spawn().map_err(|error| {
+        io::Error::new(
+            error.kind(),
+            format!(
+                "subsidiary program '{program}' could not be invoked: {}",
+                format_io_error(&error)
+            ),
+        )
+    })?;
+    let mut stdin = BufWriter::new(child.stdin.take().unwrap());
+    let result = write(&mut stdin).and_then(|differs| {
+        stdin.flush()?;
+        Ok(differs)
+    });
+    // Dropping stdin closes the pipe so the pager sees EOF. It is waited for
+    // even if writing failed, and its failure is trouble like any other.
+    drop(stdin);
+    let status = child.wait()?;
+    if !status.success() {
+        let status = match status.code() {
+            Some(code) => format!("exit status {code}"),
+            None => status.to_string(),
+        };
+        return Err(io::Error::other(format!(
+            "subsidiary program '{program}' failed ({status})"
+        )));
     }
+    result
 }
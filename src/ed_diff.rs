@@ -6,14 +6,15 @@
 use std::io::Write;
 
 use crate::params::Params;
+use crate::utils::diff_lines;
 use crate::utils::do_write_line;
 
 #[derive(Debug, PartialEq)]
-struct Mismatch {
+struct Mismatch<'a> {
     pub line_number_expected: usize,
     pub line_number_actual: usize,
-    pub expected: Vec<Vec<u8>>,
-    pub actual: Vec<Vec<u8>>,
+    pub expected: Vec<&'a [u8]>,
+    pub actual: Vec<&'a [u8]>,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -33,8 +34,8 @@ impl From<DiffError> for String {
     }
 }
 
-impl Mismatch {
-    fn new(line_number_expected: usize, line_number_actual: usize) -> Mismatch {
+impl<'a> Mismatch<'a> {
+    fn new(line_number_expected: usize, line_number_actual: usize) -> Mismatch<'a> {
         Mismatch {
             line_number_expected,
             line_number_actual,
@@ -45,7 +46,12 @@ impl Mismatch {
 }
 
 // Produces a diff between the expected output and actual output.
-fn make_diff(expected: &[u8], actual: &[u8], stop_early: bool) -> Result<Vec<Mismatch>, DiffError> {
+fn make_diff<'a>(
+    expected: &'a [u8],
+    actual: &'a [u8],
+    stop_early: bool,
+    params: &Params,
+) -> Result<Vec<Mismatch<'a>>, DiffError> {
     let mut line_number_expected = 1;
     let mut line_number_actual = 1;
     let mut results = Vec::new();
@@ -71,18 +77,18 @@ fn make_diff(expected: &[u8], actual: &[u8], stop_early: bool) -> Result<Vec<Mis
         return Err(DiffError::MissingNL);
     }
 
-    for result in diff::slice(&expected_lines, &actual_lines) {
+    for result in diff_lines(&expected_lines, &actual_lines, params) {
         match result {
             diff::Result::Left(str) => {
                 if !mismatch.actual.is_empty() {
                     results.push(mismatch);
                     mismatch = Mismatch::new(line_number_expected, line_number_actual);
                 }
-                mismatch.expected.push(str.to_vec());
+                mismatch.expected.push(str);
                 line_number_expected += 1;
             }
             diff::Result::Right(str) => {
-                mismatch.actual.push(str.to_vec());
+                mismatch.actual.push(str);
                 line_number_actual += 1;
             }
             diff::Result::Both(_str, _) => {
@@ -112,7 +118,7 @@ fn make_diff(expected: &[u8], actual: &[u8], stop_early: bool) -> Result<Vec<Mis
 
 pub fn diff(expected: &[u8], actual: &[u8], params: &Params) -> Result<Vec<u8>, DiffError> {
     let mut output = Vec::new();
-    let diff_results = make_diff(expected, actual, params.brief)?;
+    let diff_results = make_diff(expected, actual, params.brief, params)?;
     if params.brief && !diff_results.is_empty() {
         write!(&mut output, "\0").unwrap();
         return Ok(output);
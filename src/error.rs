@@ -0,0 +1,157 @@
+// This file is part of the uutils diffutils package.
+//
+// For the full copyright and license information, please view the LICENSE-*
+// files that was distributed with this source code.
+
+use std::fmt;
+
+use crate::ed_diff;
+use crate::params::{Format, Params};
+use crate::utils::{is_binary, prepare_input};
+use crate::{context_diff, json_diff, normal_diff, rcs_diff, unified_diff};
+
+/// Why [`try_diff`] produced no diff.
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DiffError {
+    /// The inputs differ, but at least one of them looks like binary data
+    /// and `params.text` is not set. The `diff` command reports this as
+    /// "Binary files ... differ".
+    Binary,
+    /// The ed format cannot express that an input lacks a final newline.
+    MissingNewline,
+}
+
+impl fmt::Display for DiffError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiffError::Binary => write!(f, "binary inputs differ"),
+            DiffError::MissingNewline => write!(f, "No newline at end of file"),
+        }
+    }
+}
+
+impl std::error::Error for DiffError {}
+
+impl From<ed_diff::DiffError> for DiffError {
+    fn from(error: ed_diff::DiffError) -> Self {
+        match error {
+            ed_diff::DiffError::MissingNL => DiffError::MissingNewline,
+        }
+    }
+}
+
+/// Diff `expected` against `actual` in the format given by `params.format`,
+/// or report why there is no diff to show. Unlike the format-specific
+/// functions, this prepares the inputs as the `diff` command does, for
+/// `params.ignore_bom`, `params.skip` and `params.strip_trailing_cr`, and
+/// treats inputs that look like binary data as it does: identical ones give
+/// an empty diff (`[]` in the JSON format), others an error unless
+/// `params.text` is set.
+///
+/// ```
+/// use diffutilslib::params::Format;
+/// use diffutilslib::{try_diff, DiffError, DiffOptions};
+///
+/// let params = DiffOptions::new().format(Format::Unified).build();
+/// assert!(try_diff(b"a\n", b"b\n", &params).is_ok());
+/// assert_eq!(try_diff(b"\0a", b"\0b", &params), Err(DiffError::Binary));
+///
+/// let params = DiffOptions::new().text(true).build();
+/// assert_eq!(try_diff(b"\0a\n", b"\0b\n", &params).unwrap(), b"1c1\n< \0a\n---\n> \0b\n");
+/// ```
+pub fn try_diff(expected: &[u8], actual: &[u8], params: &Params) -> Result<Vec<u8>, DiffError> {
+    let expected = &*prepare_input(expected, params);
+    let actual = &*prepare_input(actual, params);
+    if !params.text
+        && (is_binary(expected) || is_binary(actual))
+        && !(params.format == Format::Json && expected == actual)
+    {
+        return if expected == actual {
+            Ok(Vec::new())
+        } else {
+            Err(DiffError::Binary)
+        };
+    }
+    Ok(match params.format {
+        Format::Normal => normal_diff::diff(expected, actual, params),
+        Format::Unified => unified_diff::diff(expected, actual, params),
+        Format::Context => context_diff::diff(expected, actual, params),
+        Format::Ed => ed_diff::diff(expected, actual, params)?,
+        Format::Json => json_diff::diff(expected, actual, params),
+        Format::Rcs => rcs_diff::diff(expected, actual, params),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn text() {
+        let params = Params::default();
+        assert_eq!(
+            try_diff(b"a\n", b"b\n", &params),
+            Ok(b"1c1\n< a\n---\n> b\n".to_vec())
+        );
+        assert_eq!(try_diff(b"a\n", b"a\n", &params), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn binary() {
+        let params = Params::default();
+        assert_eq!(try_diff(b"a\0", b"b\0", &params), Err(DiffError::Binary));
+        assert_eq!(try_diff(b"a\n", b"a\0", &params), Err(DiffError::Binary));
+        assert_eq!(try_diff(b"a\0", b"a\0", &params), Ok(Vec::new()));
+        let json = Params {
+            format: Format::Json,
+            ..Default::default()
+        };
+        assert_eq!(try_diff(b"a\0", b"a\0", &json), Ok(b"[]\n".to_vec()));
+
+        // --text diffs them line by line
+        let params = Params {
+            text: true,
+            ..params
+        };
+        assert_eq!(
+            try_diff(b"a\0\n", b"b\0\n", &params),
+            Ok(b"1c1\n< a\0\n---\n> b\0\n".to_vec())
+        );
+    }
+
+    #[test]
+    fn prepared_inputs() {
+        let params = Params {
+            ignore_bom: true,
+            skip: 2,
+            strip_trailing_cr: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            try_diff(b"\xEF\xBB\xBF1\na\r\n", b"2\na\n", &params),
+            Ok(Vec::new())
+        );
+        assert_eq!(
+            try_diff(b"1\na\r\n", b"2\nb\r\n", &params),
+            Ok(b"1c1\n< a\n---\n> b\n".to_vec())
+        );
+    }
+
+    #[test]
+    fn missing_newline() {
+        let params = Params {
+            format: Format::Ed,
+            ..Default::default()
+        };
+        assert_eq!(
+            try_diff(b"a", b"b\n", &params),
+            Err(DiffError::MissingNewline)
+        );
+        assert_eq!(
+            DiffError::MissingNewline.to_string(),
+            "No newline at end of file"
+        );
+    }
+}
@@ -0,0 +1,204 @@
+// This file is part of the uutils diffutils package.
+//
+// For the full copyright and license information, please view the LICENSE-*
+// files that was distributed with this source code.
+
+use std::io::{self, Write};
+
+use crate::params::Params;
+use crate::unified_diff::{unified_hunks, DiffLine};
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Serialize the unified hunks between `expected` and `actual` as JSON.
+///
+/// The output is an array of hunks, each carrying the `old_start`,
+/// `old_lines`, `new_start` and `new_lines` of its `@@` header and a `lines`
+/// array of `{"op": "context"|"add"|"remove", "text": "..."}` entries.
+/// Lines that are not valid UTF-8 are stored base64-encoded under
+/// `text_b64` instead of `text`, and a line lacking its trailing newline is
+/// flagged with `"no_newline": true`. Identical inputs give an empty array.
+#[must_use]
+pub fn diff(expected: &[u8], actual: &[u8], params: &Params) -> Vec<u8> {
+    let mut output = Vec::new();
+    diff_to_writer(expected, actual, params, &mut output).expect("write to Vec is infallible");
+    output
+}
+
+/// Like [`diff`], but write each hunk to `output` as soon as it is
+/// serialized rather than collecting the whole array in memory. Returns
+/// whether the inputs differ.
+pub fn diff_to_writer<W: Write>(
+    expected: &[u8],
+    actual: &[u8],
+    params: &Params,
+    output: &mut W,
+) -> io::Result<bool> {
+    let hunks = unified_hunks(expected, actual, params);
+    output.write_all(b"[")?;
+    for (i, hunk) in hunks.iter().enumerate() {
+        let mut hunk_json = Vec::new();
+        if i > 0 {
+            hunk_json.push(b',');
+        }
+        write!(
+            hunk_json,
+            "{{\"old_start\":{},\"old_lines\":{},\"new_start\":{},\"new_lines\":{},\"lines\":[",
+            hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines
+        )
+        .expect("write to Vec is infallible");
+        let mut lines = hunk.lines.iter().peekable();
+        let mut first = true;
+        while let Some(line) = lines.next() {
+            let (op, text) = match line {
+                DiffLine::Context(text) => ("context", text),
+                DiffLine::Actual(text) => ("add", text),
+                DiffLine::Expected(text) => ("remove", text),
+                // Reported on the line it follows
+                DiffLine::MissingNL => continue,
+            };
+            if !first {
+                hunk_json.push(b',');
+            }
+            first = false;
+            write!(hunk_json, "{{\"op\":\"{op}\",").expect("write to Vec is infallible");
+            match std::str::from_utf8(text) {
+                Ok(text) => {
+                    hunk_json.extend_from_slice(b"\"text\":");
+                    write_json_string(&mut hunk_json, text);
+                }
+                Err(_) => {
+                    hunk_json.extend_from_slice(b"\"text_b64\":\"");
+                    write_base64(&mut hunk_json, text);
+                    hunk_json.push(b'"');
+                }
+            }
+            if lines.peek() == Some(&&DiffLine::MissingNL) {
+                hunk_json.extend_from_slice(b",\"no_newline\":true");
+            }
+            hunk_json.push(b'}');
+        }
+        hunk_json.extend_from_slice(b"]}");
+        output.write_all(&hunk_json)?;
+    }
+    output.write_all(b"]\n")?;
+    Ok(!hunks.is_empty())
+}
+
+fn write_json_string(output: &mut Vec<u8>, text: &str) {
+    output.push(b'"');
+    for c in text.chars() {
+        match c {
+            '"' => output.extend_from_slice(b"\\\""),
+            '\\' => output.extend_from_slice(b"\\\\"),
+            '\n' => output.extend_from_slice(b"\\n"),
+            '\r' => output.extend_from_slice(b"\\r"),
+            '\t' => output.extend_from_slice(b"\\t"),
+            c if c < ' ' || c == '\u{7f}' => {
+                write!(output, "\\u{:04x}", c as u32).expect("write to Vec is infallible");
+            }
+            c => {
+                let mut buf = [0; 4];
+                output.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+        }
+    }
+    output.push(b'"');
+}
+
+fn write_base64(output: &mut Vec<u8>, bytes: &[u8]) {
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize]);
+            } else {
+                output.push(b'=');
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_identical() {
+        let params = Params::default();
+        assert_eq!(diff(b"a\nb\n", b"a\nb\n", &params), b"[]\n");
+    }
+
+    #[test]
+    fn test_hunks() {
+        let params = Params {
+            context_count: 1,
+            ..Default::default()
+        };
+        let output = diff(b"a\nb\nc\n", b"a\nB\nc\n", &params);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                r#"[{"old_start":1,"old_lines":3,"new_start":1,"new_lines":3,"lines":["#,
+                r#"{"op":"context","text":"a"},"#,
+                r#"{"op":"remove","text":"b"},"#,
+                r#"{"op":"add","text":"B"},"#,
+                r#"{"op":"context","text":"c"}]}]"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_escaping_and_missing_newline() {
+        let params = Params::default();
+        let output = diff(b"\"a\\b\"\n", b"tab\there\x01", &params);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                r#"[{"old_start":1,"old_lines":1,"new_start":1,"new_lines":1,"lines":["#,
+                r#"{"op":"remove","text":"\"a\\b\""},"#,
+                r#"{"op":"add","text":"tab\there\u0001","no_newline":true}]}]"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_non_utf8() {
+        let params = Params::default();
+        let output = diff(b"caf\xe9\n", b"caf\xc3\xa9\n", &params);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                r#"[{"old_start":1,"old_lines":1,"new_start":1,"new_lines":1,"lines":["#,
+                r#"{"op":"remove","text_b64":"Y2Fm6Q=="},"#,
+                r#"{"op":"add","text":"café"}]}]"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_base64() {
+        for (input, encoded) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"\xff\xfe\xfd", "//79"),
+        ] {
+            let mut output = Vec::new();
+            write_base64(&mut output, input);
+            assert_eq!(output, encoded.as_bytes());
+        }
+    }
+}
@@ -1,14 +1,23 @@
 pub mod cmp;
 pub mod context_diff;
 pub mod ed_diff;
+pub mod error;
+pub mod json_diff;
 pub mod macros;
 pub mod normal_diff;
+pub mod options;
 pub mod params;
+pub mod rcs_diff;
 pub mod unified_diff;
 pub mod utils;
 
 // Re-export the public functions/types you need
 pub use context_diff::diff as context_diff;
 pub use ed_diff::diff as ed_diff;
+pub use error::{try_diff, DiffError};
+pub use json_diff::diff as json_diff;
 pub use normal_diff::diff as normal_diff;
+pub use options::DiffOptions;
+pub use rcs_diff::diff as rcs_diff;
 pub use unified_diff::diff as unified_diff;
+pub use unified_diff::{unified_hunks, Hunk};
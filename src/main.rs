@@ -15,9 +15,11 @@ mod cmp;
 mod context_diff;
 mod diff;
 mod ed_diff;
+mod json_diff;
 mod macros;
 mod normal_diff;
 mod params;
+mod rcs_diff;
 mod unified_diff;
 mod utils;
 
@@ -3,23 +3,26 @@
 // For the full copyright and license information, please view the LICENSE-*
 // files that was distributed with this source code.
 
-use std::io::Write;
+use std::io::{self, Write};
 
 use crate::params::Params;
+use crate::utils::diff_lines;
 use crate::utils::do_write_line;
+use crate::utils::set_color;
+use crate::utils::write_line_marker;
 
 #[derive(Debug, PartialEq)]
-struct Mismatch {
+struct Mismatch<'a> {
     pub line_number_expected: usize,
     pub line_number_actual: usize,
-    pub expected: Vec<Vec<u8>>,
-    pub actual: Vec<Vec<u8>>,
+    pub expected: Vec<&'a [u8]>,
+    pub actual: Vec<&'a [u8]>,
     pub expected_missing_nl: bool,
     pub actual_missing_nl: bool,
 }
 
-impl Mismatch {
-    fn new(line_number_expected: usize, line_number_actual: usize) -> Mismatch {
+impl<'a> Mismatch<'a> {
+    fn new(line_number_expected: usize, line_number_actual: usize) -> Mismatch<'a> {
         Mismatch {
             line_number_expected,
             line_number_actual,
@@ -32,7 +35,12 @@ impl Mismatch {
 }
 
 // Produces a diff between the expected output and actual output.
-fn make_diff(expected: &[u8], actual: &[u8], stop_early: bool) -> Vec<Mismatch> {
+fn make_diff<'a>(
+    expected: &'a [u8],
+    actual: &'a [u8],
+    stop_early: bool,
+    params: &Params,
+) -> Vec<Mismatch<'a>> {
     let mut line_number_expected = 1;
     let mut line_number_actual = 1;
     let mut results = Vec::new();
@@ -54,42 +62,43 @@ fn make_diff(expected: &[u8], actual: &[u8], stop_early: bool) -> Vec<Mismatch>
         actual_lines.pop();
     }
 
-    for result in diff::slice(&expected_lines, &actual_lines) {
+    for result in diff_lines(&expected_lines, &actual_lines, params) {
         match result {
             diff::Result::Left(str) => {
                 if !mismatch.actual.is_empty() && !mismatch.actual_missing_nl {
                     results.push(mismatch);
                     mismatch = Mismatch::new(line_number_expected, line_number_actual);
                 }
-                mismatch.expected.push(str.to_vec());
+                mismatch.expected.push(str);
                 mismatch.expected_missing_nl = line_number_expected > expected_lines_count;
                 line_number_expected += 1;
             }
             diff::Result::Right(str) => {
-                mismatch.actual.push(str.to_vec());
+                mismatch.actual.push(str);
                 mismatch.actual_missing_nl = line_number_actual > actual_lines_count;
                 line_number_actual += 1;
             }
             diff::Result::Both(str, _) => {
+                // -Z counts a missing newline as trailing white space
                 match (
                     line_number_expected > expected_lines_count,
                     line_number_actual > actual_lines_count,
                 ) {
-                    (true, false) => {
+                    (true, false) if !params.ignore_trailing_space => {
                         line_number_expected += 1;
                         line_number_actual += 1;
-                        mismatch.expected.push(str.to_vec());
+                        mismatch.expected.push(str);
                         mismatch.expected_missing_nl = true;
-                        mismatch.actual.push(str.to_vec());
+                        mismatch.actual.push(str);
                     }
-                    (false, true) => {
+                    (false, true) if !params.ignore_trailing_space => {
                         line_number_expected += 1;
                         line_number_actual += 1;
-                        mismatch.actual.push(str.to_vec());
+                        mismatch.actual.push(str);
                         mismatch.actual_missing_nl = true;
-                        mismatch.expected.push(str.to_vec());
+                        mismatch.expected.push(str);
                     }
-                    (true, true) | (false, false) => {
+                    _ => {
                         line_number_expected += 1;
                         line_number_actual += 1;
                         if !mismatch.actual.is_empty() || !mismatch.expected.is_empty() {
@@ -116,98 +125,145 @@ fn make_diff(expected: &[u8], actual: &[u8], stop_early: bool) -> Vec<Mismatch>
     results
 }
 
+/// Diff two in-memory buffers and return the output in the normal format.
+///
+/// ```
+/// use diffutilslib::normal_diff;
+/// use diffutilslib::params::Params;
+///
+/// let from = b"apple\nbanana\ncherry\n";
+/// let to = b"apple\nfig\ncherry\n";
+/// let output = normal_diff(from, to, &Params::default());
+/// assert_eq!(output, b"2c2\n< banana\n---\n> fig\n");
+///
+/// // Identical buffers produce no output.
+/// assert!(normal_diff(from, from, &Params::default()).is_empty());
+/// ```
 #[must_use]
 pub fn diff(expected: &[u8], actual: &[u8], params: &Params) -> Vec<u8> {
+    let mut output = Vec::new();
+    diff_to_writer(expected, actual, params, &mut output).expect("write to Vec is infallible");
+    output
+}
+
+/// Like [`diff`], but write each hunk to `output` as soon as it is formatted
+/// rather than collecting the whole diff in memory. Returns whether the
+/// inputs differ.
+pub fn diff_to_writer<W: Write>(
+    expected: &[u8],
+    actual: &[u8],
+    params: &Params,
+    output: &mut W,
+) -> io::Result<bool> {
     // See https://www.gnu.org/software/diffutils/manual/html_node/Detailed-Normal.html
     // for details on the syntax of the normal format.
-    let mut output = Vec::new();
-    let diff_results = make_diff(expected, actual, params.brief);
-    if params.brief && !diff_results.is_empty() {
-        write!(&mut output, "\0").unwrap();
-        return output;
+    let diff_results = make_diff(expected, actual, params.brief || params.check, params);
+    if diff_results.is_empty() {
+        return Ok(false);
     }
+    if params.brief {
+        write!(output, "\0")?;
+        return Ok(true);
+    }
+    // With -T, a tab rather than a space separates the indicator and the line
+    let separator = if params.initial_tab { "\t" } else { " " };
     for result in diff_results {
         let line_number_expected = result.line_number_expected;
         let line_number_actual = result.line_number_actual;
         let expected_count = result.expected.len();
         let actual_count = result.actual.len();
+        set_color(output, params.color, &params.palette.line_number)?;
         match (expected_count, actual_count) {
             (0, 0) => unreachable!(),
-            (0, _) => writeln!(
+            (0, 1) => write!(
                 // 'a' stands for "Add lines"
-                &mut output,
+                // exactly one line added
+                output,
+                "{}a{line_number_actual}",
+                line_number_expected - 1
+            )?,
+            (0, _) => write!(
+                // multiple lines added
+                output,
                 "{}a{},{}",
                 line_number_expected - 1,
                 line_number_actual,
                 line_number_actual + actual_count - 1
-            )
-            .unwrap(),
-            (_, 0) => writeln!(
+            )?,
+            (1, 0) => write!(
                 // 'd' stands for "Delete lines"
-                &mut output,
+                // exactly one line deleted
+                output,
+                "{line_number_expected}d{}",
+                line_number_actual - 1
+            )?,
+            (_, 0) => write!(
+                // multiple lines deleted
+                output,
                 "{},{}d{}",
                 line_number_expected,
                 expected_count + line_number_expected - 1,
                 line_number_actual - 1
-            )
-            .unwrap(),
-            (1, 1) => writeln!(
+            )?,
+            (1, 1) => write!(
                 // 'c' stands for "Change lines"
                 // exactly one line replaced by one line
-                &mut output,
+                output,
                 "{line_number_expected}c{line_number_actual}"
-            )
-            .unwrap(),
-            (1, _) => writeln!(
+            )?,
+            (1, _) => write!(
                 // one line replaced by multiple lines
-                &mut output,
+                output,
                 "{}c{},{}",
                 line_number_expected,
                 line_number_actual,
                 actual_count + line_number_actual - 1
-            )
-            .unwrap(),
-            (_, 1) => writeln!(
+            )?,
+            (_, 1) => write!(
                 // multiple lines replaced by one line
-                &mut output,
+                output,
                 "{},{}c{}",
                 line_number_expected,
                 expected_count + line_number_expected - 1,
                 line_number_actual
-            )
-            .unwrap(),
-            _ => writeln!(
+            )?,
+            _ => write!(
                 // general case: multiple lines replaced by multiple lines
-                &mut output,
+                output,
                 "{},{}c{},{}",
                 line_number_expected,
                 expected_count + line_number_expected - 1,
                 line_number_actual,
                 actual_count + line_number_actual - 1
-            )
-            .unwrap(),
+            )?,
         }
+        set_color(output, params.color, &params.palette.reset)?;
+        writeln!(output)?;
         for expected in &result.expected {
-            write!(&mut output, "< ").unwrap();
-            do_write_line(&mut output, expected, params.expand_tabs, params.tabsize).unwrap();
-            writeln!(&mut output).unwrap();
+            set_color(output, params.color, &params.palette.delete)?;
+            write_line_marker(output, "<", separator, expected, params)?;
+            do_write_line(output, expected, params.expand_tabs, params.tabsize)?;
+            set_color(output, params.color, &params.palette.reset)?;
+            writeln!(output)?;
         }
         if result.expected_missing_nl {
-            writeln!(&mut output, r"\ No newline at end of file").unwrap();
+            writeln!(output, r"\ No newline at end of file")?;
         }
         if expected_count != 0 && actual_count != 0 {
-            writeln!(&mut output, "---").unwrap();
+            writeln!(output, "---")?;
         }
         for actual in &result.actual {
-            write!(&mut output, "> ").unwrap();
-            do_write_line(&mut output, actual, params.expand_tabs, params.tabsize).unwrap();
-            writeln!(&mut output).unwrap();
+            set_color(output, params.color, &params.palette.add)?;
+            write_line_marker(output, ">", separator, actual, params)?;
+            do_write_line(output, actual, params.expand_tabs, params.tabsize)?;
+            set_color(output, params.color, &params.palette.reset)?;
+            writeln!(output)?;
         }
         if result.actual_missing_nl {
-            writeln!(&mut output, r"\ No newline at end of file").unwrap();
+            writeln!(output, r"\ No newline at end of file")?;
         }
     }
-    output
+    Ok(true)
 }
 
 #[cfg(test)]
@@ -226,6 +282,19 @@ mod tests {
         assert_eq!(diff, expected);
     }
 
+    #[test]
+    fn test_missing_newline() {
+        let params = Params::default();
+        assert_eq!(
+            diff(b"a\nb", b"a\nc\n", &params),
+            b"2c2\n< b\n\\ No newline at end of file\n---\n> c\n"
+        );
+        assert_eq!(
+            diff(b"a\n", b"a\nb", &params),
+            b"1a2\n> b\n\\ No newline at end of file\n"
+        );
+    }
+
     #[test]
     fn test_permutations() {
         let target = "target/normal-diff/";
@@ -401,7 +470,8 @@ mod tests {
     #[test]
     fn test_permutations_empty_lines() {
         let target = "target/normal-diff/";
-        // test all possible six-line files with missing newlines.
+        // test all possible six-line files with empty lines, with and without
+        // a newline at the end.
         let _ = std::fs::create_dir(target);
         for &a in &[0, 1, 2] {
             for &b in &[0, 1, 2] {
@@ -409,59 +479,75 @@ mod tests {
                     for &d in &[0, 1, 2] {
                         for &e in &[0, 1, 2] {
                             for &f in &[0, 1, 2] {
-                                use std::fs::{self, File};
-                                use std::io::Write;
-                                use std::process::Command;
-                                let mut alef = Vec::new();
-                                let mut bet = Vec::new();
-                                alef.write_all(if a == 0 { b"\n" } else { b"b\n" }).unwrap();
-                                if a != 2 {
-                                    bet.write_all(b"b\n").unwrap();
-                                }
-                                alef.write_all(if b == 0 { b"\n" } else { b"d\n" }).unwrap();
-                                if b != 2 {
-                                    bet.write_all(b"d\n").unwrap();
-                                }
-                                alef.write_all(if c == 0 { b"\n" } else { b"f\n" }).unwrap();
-                                if c != 2 {
-                                    bet.write_all(b"f\n").unwrap();
-                                }
-                                alef.write_all(if d == 0 { b"\n" } else { b"h\n" }).unwrap();
-                                if d != 2 {
-                                    bet.write_all(b"h\n").unwrap();
-                                }
-                                alef.write_all(if e == 0 { b"\n" } else { b"j\n" }).unwrap();
-                                if e != 2 {
-                                    bet.write_all(b"j\n").unwrap();
-                                }
-                                alef.write_all(if f == 0 { b"\n" } else { b"l\n" }).unwrap();
-                                if f != 2 {
-                                    bet.write_all(b"l\n").unwrap();
+                                for &g in &[0, 1, 2, 3] {
+                                    use std::fs::{self, File};
+                                    use std::io::Write;
+                                    use std::process::Command;
+                                    let mut alef = Vec::new();
+                                    let mut bet = Vec::new();
+                                    alef.write_all(if a == 0 { b"\n" } else { b"b\n" }).unwrap();
+                                    if a != 2 {
+                                        bet.write_all(b"b\n").unwrap();
+                                    }
+                                    alef.write_all(if b == 0 { b"\n" } else { b"d\n" }).unwrap();
+                                    if b != 2 {
+                                        bet.write_all(b"d\n").unwrap();
+                                    }
+                                    alef.write_all(if c == 0 { b"\n" } else { b"f\n" }).unwrap();
+                                    if c != 2 {
+                                        bet.write_all(b"f\n").unwrap();
+                                    }
+                                    alef.write_all(if d == 0 { b"\n" } else { b"h\n" }).unwrap();
+                                    if d != 2 {
+                                        bet.write_all(b"h\n").unwrap();
+                                    }
+                                    alef.write_all(if e == 0 { b"\n" } else { b"j\n" }).unwrap();
+                                    if e != 2 {
+                                        bet.write_all(b"j\n").unwrap();
+                                    }
+                                    alef.write_all(if f == 0 { b"\n" } else { b"l\n" }).unwrap();
+                                    if f != 2 {
+                                        bet.write_all(b"l\n").unwrap();
+                                    }
+                                    match g {
+                                        0 => {
+                                            alef.pop();
+                                        }
+                                        1 => {
+                                            bet.pop();
+                                        }
+                                        2 => {
+                                            alef.pop();
+                                            bet.pop();
+                                        }
+                                        3 => {}
+                                        _ => unreachable!(),
+                                    }
+                                    // This test diff is intentionally reversed.
+                                    // We want it to turn the alef into bet.
+                                    let diff = diff(&alef, &bet, &Params::default());
+                                    File::create(format!("{target}/ab_.diff"))
+                                        .unwrap()
+                                        .write_all(&diff)
+                                        .unwrap();
+                                    let mut fa = File::create(format!("{target}/alef_")).unwrap();
+                                    fa.write_all(&alef[..]).unwrap();
+                                    let mut fb = File::create(format!("{target}/bet_")).unwrap();
+                                    fb.write_all(&bet[..]).unwrap();
+                                    let _ = fa;
+                                    let _ = fb;
+                                    let output = Command::new("patch")
+                                        .arg("-p0")
+                                        .arg(format!("{target}/alef_"))
+                                        .stdin(File::open(format!("{target}/ab_.diff")).unwrap())
+                                        .output()
+                                        .unwrap();
+                                    assert!(output.status.success(), "{output:?}");
+                                    //println!("{}", String::from_utf8_lossy(&output.stdout));
+                                    //println!("{}", String::from_utf8_lossy(&output.stderr));
+                                    let alef = fs::read(format!("{target}/alef_")).unwrap();
+                                    assert_eq!(alef, bet);
                                 }
-                                // This test diff is intentionally reversed.
-                                // We want it to turn the alef into bet.
-                                let diff = diff(&alef, &bet, &Params::default());
-                                File::create(format!("{target}/ab_.diff"))
-                                    .unwrap()
-                                    .write_all(&diff)
-                                    .unwrap();
-                                let mut fa = File::create(format!("{target}/alef_")).unwrap();
-                                fa.write_all(&alef[..]).unwrap();
-                                let mut fb = File::create(format!("{target}/bet_")).unwrap();
-                                fb.write_all(&bet[..]).unwrap();
-                                let _ = fa;
-                                let _ = fb;
-                                let output = Command::new("patch")
-                                    .arg("-p0")
-                                    .arg(format!("{target}/alef_"))
-                                    .stdin(File::open(format!("{target}/ab_.diff")).unwrap())
-                                    .output()
-                                    .unwrap();
-                                assert!(output.status.success(), "{output:?}");
-                                //println!("{}", String::from_utf8_lossy(&output.stdout));
-                                //println!("{}", String::from_utf8_lossy(&output.stderr));
-                                let alef = fs::read(format!("{target}/alef_")).unwrap();
-                                assert_eq!(alef, bet);
                             }
                         }
                     }
@@ -548,6 +634,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_suppress_blank_empty() {
+        let params = Params {
+            suppress_blank_empty: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            diff(b"a\n\nb\n", b"x\n", &params),
+            b"1,3c1\n< a\n<\n< b\n---\n> x\n"
+        );
+        assert_eq!(
+            diff(b"a\n\nb\n", b"x\n", &Params::default()),
+            b"1,3c1\n< a\n< \n< b\n---\n> x\n"
+        );
+    }
+
+    #[test]
+    fn test_initial_tab() {
+        let params = Params {
+            initial_tab: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            diff(b"a\nb\n\n", b"a\nc\n", &params),
+            b"2,3c2\n<\tb\n<\t\n---\n>\tc\n"
+        );
+    }
+
     #[test]
     fn test_stop_early() {
         let from = ["a", "b", "c"].join("\n");
@@ -581,4 +695,50 @@ mod tests {
         );
         assert!(nodiff_brief.is_empty());
     }
+
+    #[test]
+    fn test_color() {
+        // As in GNU diff: the line numbers and each changed line are a span,
+        // and the separator is left plain
+        let params = Params {
+            color: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            String::from_utf8(diff(b"a\nb\n", b"c\nb\nd", &params)).unwrap(),
+            [
+                "\x1b[36m1c1\x1b[0m",
+                "\x1b[31m< a\x1b[0m",
+                "---",
+                "\x1b[32m> c\x1b[0m",
+                "\x1b[36m2a3\x1b[0m",
+                "\x1b[32m> d\x1b[0m",
+                r"\ No newline at end of file",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_diff_to_writer() {
+        let from = b"a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let to = b"a\nB\nc\nd\ne\nf\ng\nh\nI\nj";
+        for params in [
+            Params::default(),
+            Params {
+                context_count: 1,
+                brief: true,
+                ..Default::default()
+            },
+        ] {
+            let mut output = Vec::new();
+            assert!(diff_to_writer(from, to, &params, &mut output).unwrap());
+            assert_eq!(output, diff(from, to, &params));
+
+            let mut output = Vec::new();
+            assert!(!diff_to_writer(from, from, &params, &mut output).unwrap());
+            assert!(output.is_empty());
+        }
+    }
 }
@@ -0,0 +1,343 @@
+// This file is part of the uutils diffutils package.
+//
+// For the full copyright and license information, please view the LICENSE-*
+// files that was distributed with this source code.
+
+use std::ffi::OsString;
+
+use crate::params::{Format, Palette, Params};
+
+/// Builder for the [`Params`] taken by the library diff functions, also
+/// returned by [`Params::builder`].
+///
+/// Options that are not set keep the same defaults as the `diff` command:
+/// normal format, 3 lines of context and tab stops every 8 columns.
+///
+/// Every option that changes what the library functions produce has a
+/// setter. Those that only concern the `diff` command, such as where the
+/// output goes (`output`, `paginate`, `pager`), `check`,
+/// `report_identical_files` and `unidirectional_new_file`, do not; nor does
+/// `minimal`, which is accepted for compatibility and changes nothing.
+///
+/// ```
+/// use diffutilslib::params::Format;
+/// use diffutilslib::{unified_diff, DiffOptions};
+///
+/// let params = DiffOptions::new()
+///     .format(Format::Unified)
+///     .context(1)
+///     .names("old.txt", "new.txt")
+///     .build();
+/// let output = unified_diff(b"a\nb\nc\nd\n", b"a\nb\nC\nd\n", &params);
+/// let output = String::from_utf8(output).unwrap();
+/// assert!(output.starts_with("--- old.txt\t"));
+/// assert!(output.ends_with("@@ -2,3 +2,3 @@\n b\n-c\n+C\n d\n"));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DiffOptions {
+    params: Params,
+}
+
+// Here rather than in params.rs, which the `diff` binary also builds and
+// where this would be unused.
+impl Params {
+    /// Start building [`Params`] for the library diff functions, as
+    /// [`DiffOptions::new`] does.
+    #[must_use]
+    pub fn builder() -> DiffOptions {
+        DiffOptions::new()
+    }
+}
+
+impl DiffOptions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the output format. Only informational when calling one of the
+    /// format-specific functions directly.
+    #[must_use]
+    pub fn format(mut self, format: Format) -> Self {
+        self.params.format = format;
+        self
+    }
+
+    /// Set the number of context lines in context and unified output.
+    #[must_use]
+    pub fn context(mut self, lines: usize) -> Self {
+        self.params.context_count = lines;
+        self
+    }
+
+    /// Set the file names shown in the headers of context and unified
+    /// output. Their modification times are looked up by these names.
+    #[must_use]
+    pub fn names(mut self, from: impl Into<OsString>, to: impl Into<OsString>) -> Self {
+        self.params.from = from.into();
+        self.params.to = to.into();
+        self
+    }
+
+    /// Merge changes up to 16 unchanged lines apart into one hunk of unified
+    /// output, as with `-U auto`. Other hunks keep [`context`](Self::context).
+    #[must_use]
+    pub fn context_auto(mut self, context_auto: bool) -> Self {
+        self.params.context_auto = context_auto;
+        self
+    }
+
+    /// Show `from` and `to` in the headers instead of the file names and
+    /// modification times, as with `--label`.
+    #[must_use]
+    pub fn labels(mut self, from: impl Into<OsString>, to: impl Into<OsString>) -> Self {
+        self.params.from_label = Some(from.into());
+        self.params.to_label = Some(to.into());
+        self
+    }
+
+    /// Remove `count` leading components from the file names shown in the
+    /// headers, as with `--strip-paths`.
+    #[must_use]
+    pub fn strip_paths(mut self, count: usize) -> Self {
+        self.params.strip_paths = count;
+        self
+    }
+
+    /// Show the file names in the headers relative to `dir` when they are
+    /// inside it, as with `--relative-to`.
+    #[must_use]
+    pub fn relative_to(mut self, dir: impl Into<OsString>) -> Self {
+        self.params.relative_to = Some(dir.into());
+        self
+    }
+
+    /// Write the headers of `git diff` in unified output, as with `--git`.
+    #[must_use]
+    pub fn git(mut self, git: bool) -> Self {
+        self.params.git = git;
+        self
+    }
+
+    /// Ignore white space at the end of lines, as with `-Z`.
+    #[must_use]
+    pub fn ignore_trailing_space(mut self, ignore_trailing_space: bool) -> Self {
+        self.params.ignore_trailing_space = ignore_trailing_space;
+        self
+    }
+
+    /// Compare lines with their tabs expanded to spaces, as with `-E`.
+    #[must_use]
+    pub fn ignore_tab_expansion(mut self, ignore_tab_expansion: bool) -> Self {
+        self.params.ignore_tab_expansion = ignore_tab_expansion;
+        self
+    }
+
+    /// Keep this many lines of the common prefix and suffix in the lines
+    /// compared, as with `--horizon-lines`.
+    #[must_use]
+    pub fn horizon_lines(mut self, lines: usize) -> Self {
+        self.params.horizon_lines = lines;
+        self
+    }
+
+    /// Diff inputs that look like binary data line by line, as with `-a`,
+    /// instead of having [`try_diff`](crate::try_diff) report
+    /// [`DiffError::Binary`](crate::DiffError::Binary).
+    #[must_use]
+    pub fn text(mut self, text: bool) -> Self {
+        self.params.text = text;
+        self
+    }
+
+    /// Remove the carriage return before each newline of the inputs, as
+    /// with `--strip-trailing-cr`. Applied by [`try_diff`](crate::try_diff);
+    /// the format-specific functions compare their inputs as given.
+    #[must_use]
+    pub fn strip_trailing_cr(mut self, strip_trailing_cr: bool) -> Self {
+        self.params.strip_trailing_cr = strip_trailing_cr;
+        self
+    }
+
+    /// Drop a leading byte order mark from the inputs, as with
+    /// `--ignore-bom`. Applied by [`try_diff`](crate::try_diff) only.
+    #[must_use]
+    pub fn ignore_bom(mut self, ignore_bom: bool) -> Self {
+        self.params.ignore_bom = ignore_bom;
+        self
+    }
+
+    /// Discard the first `bytes` bytes of each input, as with `--skip`.
+    /// Applied by [`try_diff`](crate::try_diff) only.
+    #[must_use]
+    pub fn skip(mut self, bytes: usize) -> Self {
+        self.params.skip = bytes;
+        self
+    }
+
+    /// Only report whether the inputs differ, as with `-q`.
+    #[must_use]
+    pub fn brief(mut self, brief: bool) -> Self {
+        self.params.brief = brief;
+        self
+    }
+
+    /// Expand tabs to spaces in the output, as with `-t`.
+    #[must_use]
+    pub fn expand_tabs(mut self, expand_tabs: bool) -> Self {
+        self.params.expand_tabs = expand_tabs;
+        self
+    }
+
+    /// Set the distance between tab stops used to expand tabs.
+    ///
+    /// # Panics
+    /// Panics if `tabsize` is 0.
+    #[must_use]
+    pub fn tabsize(mut self, tabsize: usize) -> Self {
+        assert!(tabsize > 0, "tab size must be greater than 0");
+        self.params.tabsize = tabsize;
+        self
+    }
+
+    /// Start lines with a tab instead of a space, as with `-T`.
+    #[must_use]
+    pub fn initial_tab(mut self, initial_tab: bool) -> Self {
+        self.params.initial_tab = initial_tab;
+        self
+    }
+
+    /// Write empty lines without the white space after their marker, as
+    /// with `--suppress-blank-empty`.
+    #[must_use]
+    pub fn suppress_blank_empty(mut self, suppress_blank_empty: bool) -> Self {
+        self.params.suppress_blank_empty = suppress_blank_empty;
+        self
+    }
+
+    /// Show the enclosing C function in hunk headers, as with `-p`.
+    #[must_use]
+    pub fn show_c_function(mut self, show_c_function: bool) -> Self {
+        self.params.show_c_function = show_c_function;
+        self
+    }
+
+    /// Colorize normal, context and unified output with ANSI escape
+    /// sequences, as GNU diff does. The other formats are left plain.
+    #[must_use]
+    pub fn color(mut self, color: bool) -> Self {
+        self.params.color = color;
+        self
+    }
+
+    /// Set the SGR codes used by [`color`](Self::color), as with `--palette`.
+    #[must_use]
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.params.palette = palette;
+        self
+    }
+
+    #[must_use]
+    pub fn build(self) -> Params {
+        self.params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn defaults() {
+        assert_eq!(DiffOptions::new().build(), Params::default());
+    }
+
+    #[test]
+    fn builder() {
+        assert_eq!(
+            DiffOptions::new()
+                .format(Format::Context)
+                .context(5)
+                .names("foo", "bar")
+                .expand_tabs(true)
+                .tabsize(4)
+                .initial_tab(true)
+                .build(),
+            Params {
+                from: "foo".into(),
+                to: "bar".into(),
+                format: Format::Context,
+                context_count: 5,
+                expand_tabs: true,
+                tabsize: 4,
+                initial_tab: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn params_builder() {
+        assert_eq!(Params::builder().build(), Params::default());
+        assert_eq!(
+            Params::builder()
+                .format(Format::Unified)
+                .labels("a/foo", "b/foo")
+                .strip_paths(1)
+                .git(true)
+                .ignore_trailing_space(true)
+                .ignore_tab_expansion(true)
+                .build(),
+            Params {
+                format: Format::Unified,
+                from_label: Some("a/foo".into()),
+                to_label: Some("b/foo".into()),
+                strip_paths: 1,
+                git: true,
+                ignore_trailing_space: true,
+                ignore_tab_expansion: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn input_options() {
+        let palette = Palette {
+            add: "1;32".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            Params::builder()
+                .context_auto(true)
+                .text(true)
+                .strip_trailing_cr(true)
+                .ignore_bom(true)
+                .skip(3)
+                .horizon_lines(2)
+                .suppress_blank_empty(true)
+                .color(true)
+                .palette(palette.clone())
+                .build(),
+            Params {
+                context_auto: true,
+                text: true,
+                strip_trailing_cr: true,
+                ignore_bom: true,
+                skip: 3,
+                horizon_lines: 2,
+                suppress_blank_empty: true,
+                color: true,
+                palette,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "tab size must be greater than 0")]
+    fn zero_tabsize() {
+        let _ = DiffOptions::new().tabsize(0);
+    }
+}
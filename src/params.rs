@@ -1,9 +1,15 @@
-use std::ffi::OsString;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, IsTerminal};
 use std::iter::Peekable;
 use std::path::PathBuf;
 
 use regex::Regex;
 
+use crate::utils::{
+    is_posixly_correct, parse_byte_count, print_version, split_os_str, EXIT_STATUS_HELP,
+};
+
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
 pub enum Format {
     #[default]
@@ -11,6 +17,36 @@ pub enum Format {
     Unified,
     Context,
     Ed,
+    Json,
+    Rcs,
+}
+
+/// The SGR codes --color uses for each part of the output, as set with
+/// --palette. The defaults are GNU diff's.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Palette {
+    /// `rs`: the code that ends every colored span.
+    pub reset: String,
+    /// `hd`: file header lines.
+    pub header: String,
+    /// `ad`: added lines.
+    pub add: String,
+    /// `de`: deleted lines.
+    pub delete: String,
+    /// `ln`: the line numbers of hunk headers.
+    pub line_number: String,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            reset: "0".to_string(),
+            header: "1".to_string(),
+            add: "32".to_string(),
+            delete: "31".to_string(),
+            line_number: "36".to_string(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -20,10 +56,54 @@ pub struct Params {
     pub to: OsString,
     pub format: Format,
     pub context_count: usize,
+    // -U auto: in unified_diff, also merge changes up to 16 unchanged lines
+    // apart into one hunk. Other hunks keep context_count lines of context.
+    pub context_auto: bool,
+    // --git: unified output with the headers of `git diff`.
+    pub git: bool,
     pub report_identical_files: bool,
     pub brief: bool,
+    // --check: only the exit status reports whether the inputs differ;
+    // nothing is written, whatever the output format.
+    pub check: bool,
     pub expand_tabs: bool,
     pub tabsize: usize,
+    pub initial_tab: bool,
+    pub suppress_blank_empty: bool,
+    pub paginate: bool,
+    pub pager: Option<OsString>,
+    pub output: Option<OsString>,
+    pub show_c_function: bool,
+    pub from_label: Option<OsString>,
+    pub to_label: Option<OsString>,
+    pub text: bool,
+    pub strip_trailing_cr: bool,
+    // --ignore-bom: drop a UTF-8 or UTF-16 byte order mark from the start
+    // of each input. Without it, a BOM is compared like any other bytes.
+    pub ignore_bom: bool,
+    pub ignore_trailing_space: bool,
+    pub ignore_tab_expansion: bool,
+    pub unidirectional_new_file: bool,
+    // The diff crate always finds a longest common subsequence, so the diff
+    // is already minimal; this is for engines that may trade size for speed.
+    pub minimal: bool,
+    // Lines of the common prefix and suffix that are still compared when
+    // the rest is set aside, so that changes can be paired with them.
+    pub horizon_lines: usize,
+    // --strip-paths: leading components removed from the file names shown
+    // in headers, as `patch -p` would. A --label is shown as given.
+    pub strip_paths: usize,
+    // --relative-to: directory that file names under it are shown relative
+    // to in headers, before --strip-paths applies. Other names are kept.
+    pub relative_to: Option<OsString>,
+    // --skip: bytes discarded from the start of each input before it is
+    // split into lines. The rest may start mid-line, and line numbers count
+    // from the first byte kept.
+    pub skip: usize,
+    // --color: normal, context and unified output is colored with the
+    // palette; ed, RCS and JSON output stays plain, as in GNU diff.
+    pub color: bool,
+    pub palette: Palette,
 }
 
 impl Default for Params {
@@ -34,10 +114,34 @@ impl Default for Params {
             to: OsString::default(),
             format: Format::default(),
             context_count: 3,
+            context_auto: false,
+            git: false,
             report_identical_files: false,
             brief: false,
+            check: false,
             expand_tabs: false,
             tabsize: 8,
+            initial_tab: false,
+            suppress_blank_empty: false,
+            paginate: false,
+            pager: None,
+            output: None,
+            show_c_function: false,
+            from_label: None,
+            to_label: None,
+            text: false,
+            strip_trailing_cr: false,
+            ignore_bom: false,
+            ignore_trailing_space: false,
+            ignore_tab_expansion: false,
+            unidirectional_new_file: false,
+            minimal: false,
+            horizon_lines: 0,
+            strip_paths: 0,
+            relative_to: None,
+            skip: 0,
+            color: false,
+            palette: Palette::default(),
         }
     }
 }
@@ -56,7 +160,9 @@ pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Resu
     let mut to = None;
     let mut format = None;
     let mut context = None;
-    let tabsize_re = Regex::new(r"^--tabsize=(?<num>\d+)$").unwrap();
+    let posixly_correct = is_posixly_correct();
+    let mut speed_large_files = false;
+    let mut color = None;
     while let Some(param) = opts.next() {
         let next_param = opts.peek();
         if param == "--" {
@@ -65,6 +171,9 @@ pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Resu
         if param == "-" {
             if from.is_none() {
                 from = Some(param);
+                if posixly_correct {
+                    break;
+                }
             } else if to.is_none() {
                 to = Some(param);
             } else {
@@ -75,6 +184,20 @@ pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Resu
             }
             continue;
         }
+        // A long option given as --name=value, or else the whole argument
+        let (name, value) = split_long_option(&param).unzip();
+        let name = name.unwrap_or(&param);
+        if param == "--help" {
+            println!(
+                "Usage: {} <from> <to>\n\n{EXIT_STATUS_HELP}",
+                params.executable.to_string_lossy()
+            );
+            std::process::exit(0);
+        }
+        if param == "-v" || param == "--version" {
+            print_version("diff");
+            std::process::exit(0);
+        }
         if param == "-s" || param == "--report-identical-files" {
             params.report_identical_files = true;
             continue;
@@ -83,10 +206,138 @@ pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Resu
             params.brief = true;
             continue;
         }
+        if param == "--suppress-blank-empty" {
+            params.suppress_blank_empty = true;
+            continue;
+        }
+        if param == "--check" {
+            params.check = true;
+            continue;
+        }
         if param == "-t" || param == "--expand-tabs" {
             params.expand_tabs = true;
             continue;
         }
+        if param == "-T" || param == "--initial-tab" {
+            params.initial_tab = true;
+            continue;
+        }
+        if param == "-l" || param == "--paginate" {
+            params.paginate = true;
+            continue;
+        }
+        if name == "--pager" {
+            params.pager = Some(option_value(name, value, &mut opts)?);
+            continue;
+        }
+        if param == "-L" || name == "--label" {
+            // Labels are kept byte for byte, even when not valid UTF-8
+            let label = option_value(name, value, &mut opts)?;
+            if params.from_label.is_none() {
+                params.from_label = Some(label);
+            } else if params.to_label.is_none() {
+                params.to_label = Some(label);
+            } else {
+                return Err("too many file label options".to_string());
+            }
+            continue;
+        }
+        if param == "-o" || name == "--output" {
+            params.output = Some(option_value(name, value, &mut opts)?);
+            continue;
+        }
+        if param == "-p" || param == "--show-c-function" {
+            params.show_c_function = true;
+            continue;
+        }
+        if param == "-d" || param == "--minimal" {
+            params.minimal = true;
+            continue;
+        }
+        if name == "--horizon-lines" {
+            let value = option_value(name, value, &mut opts)?;
+            let value = value.to_string_lossy();
+            params.horizon_lines = match value.parse::<usize>() {
+                Ok(num) => num,
+                Err(e) if *e.kind() == std::num::IntErrorKind::PosOverflow => usize::MAX,
+                Err(_) => return Err(format!("invalid horizon length '{value}'")),
+            };
+            continue;
+        }
+        if name == "--strip-paths" {
+            let value = option_value(name, value, &mut opts)?;
+            let value = value.to_string_lossy();
+            params.strip_paths = match value.parse::<usize>() {
+                Ok(num) => num,
+                Err(e) if *e.kind() == std::num::IntErrorKind::PosOverflow => usize::MAX,
+                Err(_) => return Err(format!("invalid strip count '{value}'")),
+            };
+            continue;
+        }
+        if name == "--relative-to" {
+            params.relative_to = Some(option_value(name, value, &mut opts)?);
+            continue;
+        }
+        if name == "--skip" {
+            let value = option_value(name, value, &mut opts)?;
+            let value = value.to_string_lossy();
+            params.skip =
+                parse_byte_count(&value).map_err(|_| format!("invalid --skip value '{value}'"))?;
+            continue;
+        }
+        if param == "--speed-large-files" {
+            speed_large_files = true;
+            continue;
+        }
+        if param == "-E" || param == "--ignore-tab-expansion" {
+            params.ignore_tab_expansion = true;
+            continue;
+        }
+        if param == "-Z" || param == "--ignore-trailing-space" {
+            params.ignore_trailing_space = true;
+            continue;
+        }
+        if param == "-a" || param == "--text" {
+            params.text = true;
+            continue;
+        }
+        if param == "--binary" {
+            // Input is always read without newline translation, so CRLF and
+            // LF lines already differ on every platform.
+            continue;
+        }
+        if param == "--strip-trailing-cr" {
+            params.strip_trailing_cr = true;
+            continue;
+        }
+        if param == "--ignore-bom" {
+            params.ignore_bom = true;
+            continue;
+        }
+        if param == "--unidirectional-new-file" {
+            params.unidirectional_new_file = true;
+            continue;
+        }
+        if name == "--color" {
+            // The argument is optional, so it is never taken from the next one
+            let when = value.map_or("auto".into(), OsStr::to_string_lossy);
+            let when = when.as_ref();
+            if !["always", "never", "auto"].contains(&when) {
+                return Err(format!("invalid argument '{when}' for '--color'"));
+            }
+            color = Some(when.to_string());
+            continue;
+        }
+        if name == "--palette" {
+            let spec = option_value(name, value, &mut opts)?;
+            let spec = spec.to_string_lossy();
+            parse_palette(&spec, &mut params)?;
+            continue;
+        }
+        if param == "--no-color" {
+            color = Some("never".to_string());
+            continue;
+        }
         if param == "--normal" {
             if format.is_some() && format != Some(Format::Normal) {
                 return Err("Conflicting output style options".to_string());
@@ -94,6 +345,13 @@ pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Resu
             format = Some(Format::Normal);
             continue;
         }
+        if param == "-n" || param == "--rcs" {
+            if format.is_some() && format != Some(Format::Rcs) {
+                return Err("Conflicting output style options".to_string());
+            }
+            format = Some(Format::Rcs);
+            continue;
+        }
         if param == "-e" || param == "--ed" {
             if format.is_some() && format != Some(Format::Ed) {
                 return Err("Conflicting output style options".to_string());
@@ -101,22 +359,46 @@ pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Resu
             format = Some(Format::Ed);
             continue;
         }
-        if tabsize_re.is_match(param.to_string_lossy().as_ref()) {
-            // Because param matches the regular expression,
-            // it is safe to assume it is valid UTF-8.
-            let param = param.into_string().unwrap();
-            let tabsize_str = tabsize_re
-                .captures(param.as_str())
-                .unwrap()
-                .name("num")
-                .unwrap()
-                .as_str();
+        if name == "--format" {
+            let value = option_value(name, value, &mut opts)?;
+            let value = value.to_string_lossy();
+            if value != "json" {
+                return Err(format!("invalid argument '{value}' for '--format'"));
+            }
+            if format.is_some() && format != Some(Format::Json) {
+                return Err("Conflicting output style options".to_string());
+            }
+            format = Some(Format::Json);
+            continue;
+        }
+        if name == "--tabsize" {
+            let tabsize_str = option_value(name, value, &mut opts)?;
+            let tabsize_str = tabsize_str.to_string_lossy();
             params.tabsize = match tabsize_str.parse::<usize>() {
-                Ok(num) => num,
-                Err(_) => return Err(format!("invalid tabsize «{tabsize_str}»")),
+                Ok(num) if num > 0 => num,
+                _ => return Err(format!("invalid tabsize «{tabsize_str}»")),
             };
             continue;
         }
+        if param == "-G" || param == "--git" {
+            if format.is_some() && format != Some(Format::Unified) {
+                return Err("Conflicting output style options".to_string());
+            }
+            format = Some(Format::Unified);
+            params.git = true;
+            continue;
+        }
+        if param == "--unified=auto" || param == "-U" && next_param.is_some_and(|p| p == "auto") {
+            if format.is_some() && format != Some(Format::Unified) {
+                return Err("Conflicting output style options".to_string());
+            }
+            format = Some(Format::Unified);
+            params.context_auto = true;
+            if param == "-U" {
+                opts.next();
+            }
+            continue;
+        }
         match match_context_diff_params(&param, next_param, format) {
             Ok(DiffStyleMatch {
                 is_match,
@@ -146,6 +428,7 @@ pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Resu
                     format = Some(Format::Unified);
                     if context_count.is_some() {
                         context = context_count;
+                        params.context_auto = false;
                     }
                     if next_param_consumed {
                         opts.next();
@@ -160,6 +443,9 @@ pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Resu
         }
         if from.is_none() {
             from = Some(param);
+            if posixly_correct {
+                break;
+            }
         } else if to.is_none() {
             to = Some(param);
         } else {
@@ -169,6 +455,14 @@ pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Resu
             ));
         }
     }
+    if params.paginate && params.output.as_ref().is_some_and(|output| output != "-") {
+        return Err("options '--paginate' and '--output' are mutually exclusive".to_string());
+    }
+    if params.minimal && speed_large_files {
+        return Err(
+            "options '--minimal' and '--speed-large-files' are mutually exclusive".to_string(),
+        );
+    }
     params.from = if let Some(from) = from {
         from
     } else if let Some(param) = opts.next() {
@@ -189,6 +483,12 @@ pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Resu
             params.executable.to_string_lossy()
         ));
     };
+    if opts.next().is_some() {
+        return Err(format!(
+            "Usage: {} <from> <to>",
+            params.executable.to_string_lossy()
+        ));
+    }
 
     // diff DIRECTORY FILE => diff DIRECTORY/FILE FILE
     // diff FILE DIRECTORY => diff FILE DIRECTORY/FILE
@@ -207,9 +507,96 @@ pub fn parse_params<I: Iterator<Item = OsString>>(mut opts: Peekable<I>) -> Resu
     if let Some(context_count) = context {
         params.context_count = context_count;
     }
+    // auto depends on where the output goes, known only once all is parsed
+    if let Some(when) = color {
+        params.color = use_color(&when, &params);
+    }
     Ok(params)
 }
 
+// Split a long option written as `--name=value` into its name and value.
+fn split_long_option(param: &OsStr) -> Option<(&OsStr, &OsStr)> {
+    let bytes = param.as_encoded_bytes();
+    if !bytes.starts_with(b"--") {
+        return None;
+    }
+    let equals = bytes.iter().position(|&byte| byte == b'=')?;
+    let (name, value) = split_os_str(param, equals);
+    Some((name, split_os_str(value, 1).1))
+}
+
+// The argument of an option that requires one: the value given with
+// `--name=value`, or else the next argument.
+fn option_value<I: Iterator<Item = OsString>>(
+    name: &OsStr,
+    value: Option<&OsStr>,
+    opts: &mut Peekable<I>,
+) -> Result<OsString, String> {
+    match value {
+        Some(value) => Ok(value.to_os_string()),
+        None => opts
+            .next()
+            .ok_or_else(|| format!("option '{}' requires an argument", name.to_string_lossy())),
+    }
+}
+
+// Decide whether --color=WHEN colorizes the output. An explicit always or
+// never is obeyed as is. With auto, a non-empty NO_COLOR disables color,
+// then CLICOLOR_FORCE (unless empty or 0) enables it, and failing both,
+// color is used only when the diff goes straight to a terminal: not to an
+// --output file, nor through the -l pager, even when stdout is one.
+fn use_color(when: &str, params: &Params) -> bool {
+    match when {
+        "always" => true,
+        "auto" => {
+            if env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+                false
+            } else if env::var_os("CLICOLOR_FORCE")
+                .is_some_and(|value| !value.is_empty() && value != "0")
+            {
+                true
+            } else {
+                !params.paginate
+                    && params.output.as_ref().is_none_or(|output| output == "-")
+                    && io::stdout().is_terminal()
+            }
+        }
+        _ => false,
+    }
+}
+
+// Apply a --palette SPEC such as `ad=32:de=31:ln=36` to params.palette. As in
+// GNU diff, keys that are not known are ignored, here with a warning; a value
+// must be made of SGR parameters, that is digits and semicolons.
+fn parse_palette(spec: &str, params: &mut Params) -> Result<(), String> {
+    for entry in spec.split(':').filter(|entry| !entry.is_empty()) {
+        let Some((key, value)) = entry.split_once('=') else {
+            return Err(format!("invalid palette entry '{entry}'"));
+        };
+        if !value.bytes().all(|b| b.is_ascii_digit() || b == b';') {
+            return Err(format!(
+                "invalid color code '{value}' for '{key}' in '--palette'"
+            ));
+        }
+        let code = match key {
+            "rs" => &mut params.palette.reset,
+            "hd" => &mut params.palette.header,
+            "ad" => &mut params.palette.add,
+            "de" => &mut params.palette.delete,
+            "ln" => &mut params.palette.line_number,
+            _ => {
+                eprintln!(
+                    "{}: warning: ignoring unknown palette key '{key}'",
+                    params.executable.to_string_lossy()
+                );
+                continue;
+            }
+        };
+        *code = value.to_string();
+    }
+    Ok(())
+}
+
 struct DiffStyleMatch {
     is_match: bool,
     context_count: Option<usize>,
@@ -240,8 +627,8 @@ fn match_context_diff_params(
                 context_count = Some(numvalue.as_str().parse::<usize>().unwrap());
             }
         }
-        if param == "-C" && next_param.is_some() {
-            match next_param.unwrap().to_string_lossy().parse::<usize>() {
+        if let Some(next_param) = next_param.filter(|_| param == "-C") {
+            match next_param.to_string_lossy().parse::<usize>() {
                 Ok(context_size) => {
                     context_count = Some(context_size);
                     next_param_consumed = true;
@@ -249,7 +636,7 @@ fn match_context_diff_params(
                 Err(_) => {
                     return Err(format!(
                         "invalid context length '{}'",
-                        next_param.unwrap().to_string_lossy()
+                        next_param.to_string_lossy()
                     ))
                 }
             }
@@ -286,8 +673,8 @@ fn match_unified_diff_params(
                 context_count = Some(numvalue.as_str().parse::<usize>().unwrap());
             }
         }
-        if param == "-U" && next_param.is_some() {
-            match next_param.unwrap().to_string_lossy().parse::<usize>() {
+        if let Some(next_param) = next_param.filter(|_| param == "-U") {
+            match next_param.to_string_lossy().parse::<usize>() {
                 Ok(context_size) => {
                     context_count = Some(context_size);
                     next_param_consumed = true;
@@ -295,7 +682,7 @@ fn match_unified_diff_params(
                 Err(_) => {
                     return Err(format!(
                         "invalid context length '{}'",
-                        next_param.unwrap().to_string_lossy()
+                        next_param.to_string_lossy()
                     ))
                 }
             }
@@ -484,6 +871,117 @@ mod tests {
         }
     }
     #[test]
+    fn context_auto() {
+        for args in [
+            vec!["-U", "auto"],
+            vec!["--unified=auto"],
+            vec!["-U5", "-U", "auto"],
+        ] {
+            let mut params = vec!["diff"];
+            params.extend(args);
+            params.extend(["foo", "bar"]);
+            assert_eq!(
+                Ok(Params {
+                    executable: os("diff"),
+                    from: os("foo"),
+                    to: os("bar"),
+                    format: Format::Unified,
+                    context_count: if params.len() == 6 { 5 } else { 3 },
+                    context_auto: true,
+                    ..Default::default()
+                }),
+                parse_params(params.iter().map(|x| os(x)).peekable())
+            );
+        }
+        // A later context length overrides auto
+        assert_eq!(
+            Ok(Params {
+                executable: os("diff"),
+                from: os("foo"),
+                to: os("bar"),
+                format: Format::Unified,
+                context_count: 5,
+                ..Default::default()
+            }),
+            parse_params(
+                [
+                    os("diff"),
+                    os("-U"),
+                    os("auto"),
+                    os("-U5"),
+                    os("foo"),
+                    os("bar")
+                ]
+                .iter()
+                .cloned()
+                .peekable()
+            )
+        );
+        assert_eq!(
+            Err("Conflicting output style options".to_string()),
+            parse_params(
+                [
+                    os("diff"),
+                    os("-c"),
+                    os("-U"),
+                    os("auto"),
+                    os("foo"),
+                    os("bar")
+                ]
+                .iter()
+                .cloned()
+                .peekable()
+            )
+        );
+    }
+    #[test]
+    fn git() {
+        for option in ["-G", "--git"] {
+            assert_eq!(
+                Ok(Params {
+                    executable: os("diff"),
+                    from: os("foo"),
+                    to: os("bar"),
+                    format: Format::Unified,
+                    git: true,
+                    ..Default::default()
+                }),
+                parse_params(
+                    [os("diff"), os(option), os("foo"), os("bar")]
+                        .iter()
+                        .cloned()
+                        .peekable()
+                )
+            );
+        }
+        assert_eq!(
+            Ok(Params {
+                executable: os("diff"),
+                from: os("foo"),
+                to: os("bar"),
+                format: Format::Unified,
+                context_count: 1,
+                git: true,
+                ..Default::default()
+            }),
+            parse_params(
+                [os("diff"), os("-U1"), os("--git"), os("foo"), os("bar")]
+                    .iter()
+                    .cloned()
+                    .peekable()
+            )
+        );
+        assert_eq!(
+            Err("Conflicting output style options".to_string()),
+            parse_params(
+                [os("diff"), os("-c"), os("--git"), os("foo"), os("bar")]
+                    .iter()
+                    .cloned()
+                    .peekable()
+            )
+        );
+    }
+    #[test]
     fn context_count() {
         assert_eq!(
             Ok(Params {
@@ -684,7 +1182,7 @@ mod tests {
         }
     }
     #[test]
-    fn tabsize() {
+    fn initial_tab() {
         assert_eq!(
             Ok(Params {
                 executable: os("diff"),
@@ -699,58 +1197,820 @@ mod tests {
                     .peekable()
             )
         );
-        assert_eq!(
-            Ok(Params {
-                executable: os("diff"),
-                from: os("foo"),
-                to: os("bar"),
-                tabsize: 0,
-                ..Default::default()
-            }),
-            parse_params(
-                [os("diff"), os("--tabsize=0"), os("foo"), os("bar")]
-                    .iter()
-                    .cloned()
-                    .peekable()
-            )
-        );
-        assert_eq!(
-            Ok(Params {
-                executable: os("diff"),
-                from: os("foo"),
-                to: os("bar"),
-                tabsize: 42,
-                ..Default::default()
-            }),
-            parse_params(
-                [os("diff"), os("--tabsize=42"), os("foo"), os("bar")]
-                    .iter()
-                    .cloned()
-                    .peekable()
-            )
-        );
-        assert!(parse_params(
-            [os("diff"), os("--tabsize"), os("foo"), os("bar")]
-                .iter()
-                .cloned()
-                .peekable()
-        )
-        .is_err());
-        assert!(parse_params(
-            [os("diff"), os("--tabsize="), os("foo"), os("bar")]
-                .iter()
-                .cloned()
-                .peekable()
-        )
-        .is_err());
-        assert!(parse_params(
-            [os("diff"), os("--tabsize=r2"), os("foo"), os("bar")]
-                .iter()
-                .cloned()
-                .peekable()
-        )
-        .is_err());
-        assert!(parse_params(
+        for option in ["-T", "--initial-tab"] {
+            assert_eq!(
+                Ok(Params {
+                    executable: os("diff"),
+                    from: os("foo"),
+                    to: os("bar"),
+                    initial_tab: true,
+                    ..Default::default()
+                }),
+                parse_params(
+                    [os("diff"), os(option), os("foo"), os("bar")]
+                        .iter()
+                        .cloned()
+                        .peekable()
+                )
+            );
+        }
+    }
+    #[test]
+    fn paginate() {
+        for option in ["-l", "--paginate"] {
+            assert_eq!(
+                Ok(Params {
+                    executable: os("diff"),
+                    from: os("foo"),
+                    to: os("bar"),
+                    paginate: true,
+                    ..Default::default()
+                }),
+                parse_params(
+                    [os("diff"), os(option), os("foo"), os("bar")]
+                        .iter()
+                        .cloned()
+                        .peekable()
+                )
+            );
+        }
+        for args in [vec!["--pager=cat"], vec!["--pager", "cat"]] {
+            let mut params = vec!["diff", "-l"];
+            params.extend(args);
+            params.extend(["foo", "bar"]);
+            assert_eq!(
+                Ok(Params {
+                    executable: os("diff"),
+                    from: os("foo"),
+                    to: os("bar"),
+                    paginate: true,
+                    pager: Some(os("cat")),
+                    ..Default::default()
+                }),
+                parse_params(params.iter().map(|x| os(x)).peekable())
+            );
+        }
+        assert!(parse_params(
+            [os("diff"), os("foo"), os("bar"), os("--pager")]
+                .iter()
+                .cloned()
+                .peekable()
+        )
+        .is_err());
+    }
+    #[test]
+    fn output() {
+        for args in [
+            vec!["-o", "out"],
+            vec!["--output", "out"],
+            vec!["--output=out"],
+        ] {
+            let mut params = vec!["diff"];
+            params.extend(args);
+            params.extend(["foo", "bar"]);
+            assert_eq!(
+                Ok(Params {
+                    executable: os("diff"),
+                    from: os("foo"),
+                    to: os("bar"),
+                    output: Some(os("out")),
+                    ..Default::default()
+                }),
+                parse_params(params.iter().map(|x| os(x)).peekable())
+            );
+        }
+        assert!(parse_params(
+            [os("diff"), os("foo"), os("bar"), os("-o")]
+                .into_iter()
+                .peekable()
+        )
+        .is_err());
+        assert_eq!(
+            Err("options '--paginate' and '--output' are mutually exclusive".to_string()),
+            parse_params(
+                [
+                    os("diff"),
+                    os("-l"),
+                    os("-o"),
+                    os("out"),
+                    os("foo"),
+                    os("bar")
+                ]
+                .into_iter()
+                .peekable()
+            )
+        );
+        // "-" is stdout, which pagination also writes to
+        assert!(parse_params(
+            [
+                os("diff"),
+                os("-l"),
+                os("-o"),
+                os("-"),
+                os("foo"),
+                os("bar")
+            ]
+            .into_iter()
+            .peekable()
+        )
+        .is_ok());
+    }
+    #[test]
+    fn horizon_lines() {
+        for args in [vec!["--horizon-lines=3"], vec!["--horizon-lines", "3"]] {
+            let mut params = vec!["diff"];
+            params.extend(args);
+            params.extend(["foo", "bar"]);
+            assert_eq!(
+                Ok(Params {
+                    executable: os("diff"),
+                    from: os("foo"),
+                    to: os("bar"),
+                    horizon_lines: 3,
+                    ..Default::default()
+                }),
+                parse_params(params.iter().map(|x| os(x)).peekable())
+            );
+        }
+        for value in ["x", "-1", ""] {
+            assert_eq!(
+                Err(format!("invalid horizon length '{value}'")),
+                parse_params(
+                    [
+                        os("diff"),
+                        os(&format!("--horizon-lines={value}")),
+                        os("foo"),
+                        os("bar")
+                    ]
+                    .iter()
+                    .cloned()
+                    .peekable()
+                )
+            );
+        }
+    }
+    #[test]
+    fn palette() {
+        let parse = |args: &[&str]| {
+            let mut params = vec!["diff"];
+            params.extend(args);
+            params.extend(["foo", "bar"]);
+            parse_params(params.iter().map(|x| os(x)).peekable())
+        };
+        assert_eq!(
+            Ok(Palette {
+                add: "1;34".to_string(),
+                delete: "35".to_string(),
+                ..Default::default()
+            }),
+            parse(&["--palette=ad=1;34:de=35"]).map(|params| params.palette)
+        );
+        // Later settings win, and unknown keys are ignored
+        assert_eq!(
+            Ok(Palette {
+                line_number: "33".to_string(),
+                ..Default::default()
+            }),
+            parse(&["--palette", "ln=34:xx=1", "--palette=ln=33:"]).map(|params| params.palette)
+        );
+        assert_eq!(
+            Err("invalid color code 'red' for 'ad' in '--palette'".to_string()),
+            parse(&["--palette=ad=red"])
+        );
+        assert_eq!(
+            Err("invalid palette entry 'ad'".to_string()),
+            parse(&["--palette=ad"])
+        );
+    }
+
+    #[test]
+    fn suppress_blank_empty() {
+        assert_eq!(
+            Ok(Params {
+                executable: os("diff"),
+                from: os("foo"),
+                to: os("bar"),
+                suppress_blank_empty: true,
+                ..Default::default()
+            }),
+            parse_params(
+                [
+                    os("diff"),
+                    os("--suppress-blank-empty"),
+                    os("foo"),
+                    os("bar")
+                ]
+                .iter()
+                .cloned()
+                .peekable()
+            )
+        );
+    }
+
+    #[test]
+    fn check() {
+        assert_eq!(
+            Ok(Params {
+                executable: os("diff"),
+                from: os("foo"),
+                to: os("bar"),
+                format: Format::Unified,
+                check: true,
+                ..Default::default()
+            }),
+            parse_params(
+                [os("diff"), os("-u"), os("--check"), os("foo"), os("bar")]
+                    .iter()
+                    .cloned()
+                    .peekable()
+            )
+        );
+    }
+    #[test]
+    fn skip() {
+        for (args, skip) in [
+            (vec!["--skip=10"], 10),
+            (vec!["--skip", "10"], 10),
+            (vec!["--skip=2K"], 2048),
+            (vec!["--skip=1kB"], 1000),
+        ] {
+            let mut params = vec!["diff"];
+            params.extend(args);
+            params.extend(["foo", "bar"]);
+            assert_eq!(
+                Ok(Params {
+                    executable: os("diff"),
+                    from: os("foo"),
+                    to: os("bar"),
+                    skip,
+                    ..Default::default()
+                }),
+                parse_params(params.iter().map(|x| os(x)).peekable())
+            );
+        }
+        for value in ["x", "-1", "", "5Q"] {
+            assert_eq!(
+                Err(format!("invalid --skip value '{value}'")),
+                parse_params(
+                    [
+                        os("diff"),
+                        os(&format!("--skip={value}")),
+                        os("foo"),
+                        os("bar")
+                    ]
+                    .iter()
+                    .cloned()
+                    .peekable()
+                )
+            );
+        }
+    }
+    #[test]
+    fn split_long_option() {
+        fn split(param: &str) -> Option<(&str, &str)> {
+            super::split_long_option(OsStr::new(param))
+                .map(|(name, value)| (name.to_str().unwrap(), value.to_str().unwrap()))
+        }
+        assert_eq!(split("--label=a=b"), Some(("--label", "a=b")));
+        assert_eq!(split("--label="), Some(("--label", "")));
+        assert_eq!(split("--label"), None);
+        assert_eq!(split("-L=a"), None);
+        assert_eq!(split("a=b"), None);
+    }
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_option_values() {
+        use std::os::unix::ffi::OsStringExt;
+        let value = OsString::from_vec(b"f\xFFo".to_vec());
+        let joined = |name: &str| OsString::from_vec([name.as_bytes(), b"=f\xFFo"].concat());
+        for args in [
+            vec![
+                joined("--output"),
+                joined("--relative-to"),
+                joined("--pager"),
+            ],
+            vec![
+                os("--output"),
+                value.clone(),
+                os("--relative-to"),
+                value.clone(),
+                os("--pager"),
+                value.clone(),
+            ],
+        ] {
+            let mut params = vec![os("diff")];
+            params.extend(args);
+            params.extend([os("foo"), os("bar")]);
+            assert_eq!(
+                Ok(Params {
+                    executable: os("diff"),
+                    from: os("foo"),
+                    to: os("bar"),
+                    output: Some(value.clone()),
+                    relative_to: Some(value.clone()),
+                    pager: Some(value.clone()),
+                    ..Default::default()
+                }),
+                parse_params(params.into_iter().peekable())
+            );
+        }
+    }
+    #[test]
+    fn strip_paths() {
+        for args in [vec!["--strip-paths=1"], vec!["--strip-paths", "1"]] {
+            let mut params = vec!["diff"];
+            params.extend(args);
+            params.extend(["foo", "bar"]);
+            assert_eq!(
+                Ok(Params {
+                    executable: os("diff"),
+                    from: os("foo"),
+                    to: os("bar"),
+                    strip_paths: 1,
+                    ..Default::default()
+                }),
+                parse_params(params.iter().map(|x| os(x)).peekable())
+            );
+        }
+        for value in ["x", "-1", ""] {
+            assert_eq!(
+                Err(format!("invalid strip count '{value}'")),
+                parse_params(
+                    [
+                        os("diff"),
+                        os(&format!("--strip-paths={value}")),
+                        os("foo"),
+                        os("bar")
+                    ]
+                    .iter()
+                    .cloned()
+                    .peekable()
+                )
+            );
+        }
+    }
+    #[test]
+    fn relative_to() {
+        for args in [vec!["--relative-to=/tmp"], vec!["--relative-to", "/tmp"]] {
+            let mut params = vec!["diff"];
+            params.extend(args);
+            params.extend(["foo", "bar"]);
+            assert_eq!(
+                Ok(Params {
+                    executable: os("diff"),
+                    from: os("foo"),
+                    to: os("bar"),
+                    relative_to: Some(os("/tmp")),
+                    ..Default::default()
+                }),
+                parse_params(params.iter().map(|x| os(x)).peekable())
+            );
+        }
+        assert!(parse_params(
+            [os("diff"), os("foo"), os("bar"), os("--relative-to")]
+                .iter()
+                .cloned()
+                .peekable()
+        )
+        .is_err());
+    }
+    #[test]
+    fn strip_trailing_cr() {
+        assert_eq!(
+            Ok(Params {
+                executable: os("diff"),
+                from: os("foo"),
+                to: os("bar"),
+                strip_trailing_cr: true,
+                ..Default::default()
+            }),
+            parse_params(
+                [os("diff"), os("--strip-trailing-cr"), os("foo"), os("bar")]
+                    .iter()
+                    .cloned()
+                    .peekable()
+            )
+        );
+    }
+    #[test]
+    fn ignore_bom() {
+        assert_eq!(
+            Ok(Params {
+                executable: os("diff"),
+                from: os("foo"),
+                to: os("bar"),
+                ignore_bom: true,
+                ..Default::default()
+            }),
+            parse_params(
+                [os("diff"), os("--ignore-bom"), os("foo"), os("bar")]
+                    .iter()
+                    .cloned()
+                    .peekable()
+            )
+        );
+    }
+    #[test]
+    fn ignore_trailing_space() {
+        for option in ["-Z", "--ignore-trailing-space"] {
+            assert_eq!(
+                Ok(Params {
+                    executable: os("diff"),
+                    from: os("foo"),
+                    to: os("bar"),
+                    ignore_trailing_space: true,
+                    ..Default::default()
+                }),
+                parse_params(
+                    [os("diff"), os(option), os("foo"), os("bar")]
+                        .iter()
+                        .cloned()
+                        .peekable()
+                )
+            );
+        }
+    }
+    #[test]
+    fn ignore_tab_expansion() {
+        for option in ["-E", "--ignore-tab-expansion"] {
+            assert_eq!(
+                Ok(Params {
+                    executable: os("diff"),
+                    from: os("foo"),
+                    to: os("bar"),
+                    ignore_tab_expansion: true,
+                    ..Default::default()
+                }),
+                parse_params(
+                    [os("diff"), os(option), os("foo"), os("bar")]
+                        .iter()
+                        .cloned()
+                        .peekable()
+                )
+            );
+        }
+    }
+    #[test]
+    fn label() {
+        assert_eq!(
+            Ok(Params {
+                executable: os("diff"),
+                from: os("foo"),
+                to: os("bar"),
+                from_label: Some(os("a")),
+                to_label: Some(os("b")),
+                ..Default::default()
+            }),
+            parse_params(
+                [
+                    os("diff"),
+                    os("--label"),
+                    os("a"),
+                    os("--label=b"),
+                    os("foo"),
+                    os("bar")
+                ]
+                .iter()
+                .cloned()
+                .peekable()
+            )
+        );
+        assert_eq!(
+            Ok(Params {
+                executable: os("diff"),
+                from: os("foo"),
+                to: os("bar"),
+                from_label: Some(os("a")),
+                ..Default::default()
+            }),
+            parse_params(
+                [os("diff"), os("-L"), os("a"), os("foo"), os("bar")]
+                    .iter()
+                    .cloned()
+                    .peekable()
+            )
+        );
+        assert_eq!(
+            Err("too many file label options".to_string()),
+            parse_params(
+                [
+                    os("diff"),
+                    os("--label=a"),
+                    os("--label=b"),
+                    os("--label=c"),
+                    os("foo"),
+                    os("bar")
+                ]
+                .iter()
+                .cloned()
+                .peekable()
+            )
+        );
+        assert!(parse_params([os("diff"), os("--label")].iter().cloned().peekable()).is_err());
+    }
+    #[test]
+    fn text() {
+        for param in ["-a", "--text"] {
+            assert_eq!(
+                Ok(Params {
+                    executable: os("diff"),
+                    from: os("foo"),
+                    to: os("bar"),
+                    text: true,
+                    ..Default::default()
+                }),
+                parse_params(
+                    [os("diff"), os(param), os("foo"), os("bar")]
+                        .iter()
+                        .cloned()
+                        .peekable()
+                )
+            );
+        }
+    }
+    #[test]
+    fn binary() {
+        assert_eq!(
+            Ok(Params {
+                executable: os("diff"),
+                from: os("foo"),
+                to: os("bar"),
+                ..Default::default()
+            }),
+            parse_params(
+                [os("diff"), os("--binary"), os("foo"), os("bar")]
+                    .iter()
+                    .cloned()
+                    .peekable()
+            )
+        );
+    }
+    #[test]
+    fn unidirectional_new_file() {
+        assert_eq!(
+            Ok(Params {
+                executable: os("diff"),
+                from: os("foo"),
+                to: os("bar"),
+                unidirectional_new_file: true,
+                ..Default::default()
+            }),
+            parse_params(
+                [
+                    os("diff"),
+                    os("--unidirectional-new-file"),
+                    os("foo"),
+                    os("bar")
+                ]
+                .iter()
+                .cloned()
+                .peekable()
+            )
+        );
+    }
+    #[test]
+    fn show_c_function() {
+        for option in ["-p", "--show-c-function"] {
+            assert_eq!(
+                Ok(Params {
+                    executable: os("diff"),
+                    from: os("foo"),
+                    to: os("bar"),
+                    show_c_function: true,
+                    ..Default::default()
+                }),
+                parse_params(
+                    [os("diff"), os(option), os("foo"), os("bar")]
+                        .iter()
+                        .cloned()
+                        .peekable()
+                )
+            );
+        }
+    }
+    #[test]
+    fn color() {
+        for (option, color) in [
+            ("--color=always", true),
+            ("--color=never", false),
+            ("--no-color", false),
+        ] {
+            assert_eq!(
+                Ok(Params {
+                    executable: os("diff"),
+                    from: os("foo"),
+                    to: os("bar"),
+                    color,
+                    ..Default::default()
+                }),
+                parse_params(
+                    [os("diff"), os(option), os("foo"), os("bar")]
+                        .iter()
+                        .cloned()
+                        .peekable()
+                )
+            );
+        }
+        assert!(parse_params(
+            [os("diff"), os("--color=sometimes"), os("foo"), os("bar")]
+                .iter()
+                .cloned()
+                .peekable()
+        )
+        .is_err());
+    }
+    #[test]
+    fn minimal() {
+        for option in ["-d", "--minimal"] {
+            assert_eq!(
+                Ok(Params {
+                    executable: os("diff"),
+                    from: os("foo"),
+                    to: os("bar"),
+                    minimal: true,
+                    ..Default::default()
+                }),
+                parse_params(
+                    [os("diff"), os(option), os("foo"), os("bar")]
+                        .iter()
+                        .cloned()
+                        .peekable()
+                )
+            );
+        }
+        assert_eq!(
+            Ok(Params {
+                executable: os("diff"),
+                from: os("foo"),
+                to: os("bar"),
+                ..Default::default()
+            }),
+            parse_params(
+                [os("diff"), os("--speed-large-files"), os("foo"), os("bar")]
+                    .iter()
+                    .cloned()
+                    .peekable()
+            )
+        );
+        for options in [
+            ["-d", "--speed-large-files"],
+            ["--speed-large-files", "--minimal"],
+        ] {
+            assert!(parse_params(
+                [
+                    os("diff"),
+                    os(options[0]),
+                    os(options[1]),
+                    os("foo"),
+                    os("bar")
+                ]
+                .iter()
+                .cloned()
+                .peekable()
+            )
+            .is_err());
+        }
+    }
+    #[test]
+    fn rcs() {
+        for option in ["-n", "--rcs"] {
+            assert_eq!(
+                Ok(Params {
+                    executable: os("diff"),
+                    from: os("foo"),
+                    to: os("bar"),
+                    format: Format::Rcs,
+                    ..Default::default()
+                }),
+                parse_params(
+                    [os("diff"), os(option), os("foo"), os("bar")]
+                        .iter()
+                        .cloned()
+                        .peekable()
+                )
+            );
+        }
+        assert!(parse_params(
+            [os("diff"), os("-n"), os("-e"), os("foo"), os("bar")]
+                .iter()
+                .cloned()
+                .peekable()
+        )
+        .is_err());
+    }
+    #[test]
+    fn json_format() {
+        assert_eq!(
+            Ok(Params {
+                executable: os("diff"),
+                from: os("foo"),
+                to: os("bar"),
+                format: Format::Json,
+                ..Default::default()
+            }),
+            parse_params(
+                [os("diff"), os("--format=json"), os("foo"), os("bar")]
+                    .iter()
+                    .cloned()
+                    .peekable()
+            )
+        );
+        assert!(parse_params(
+            [os("diff"), os("--format=xml"), os("foo"), os("bar")]
+                .iter()
+                .cloned()
+                .peekable()
+        )
+        .is_err());
+        assert!(parse_params(
+            [
+                os("diff"),
+                os("-u"),
+                os("--format=json"),
+                os("foo"),
+                os("bar")
+            ]
+            .iter()
+            .cloned()
+            .peekable()
+        )
+        .is_err());
+    }
+    #[test]
+    fn tabsize() {
+        assert_eq!(
+            Ok(Params {
+                executable: os("diff"),
+                from: os("foo"),
+                to: os("bar"),
+                ..Default::default()
+            }),
+            parse_params(
+                [os("diff"), os("foo"), os("bar")]
+                    .iter()
+                    .cloned()
+                    .peekable()
+            )
+        );
+        assert_eq!(
+            Ok(Params {
+                executable: os("diff"),
+                from: os("foo"),
+                to: os("bar"),
+                tabsize: 42,
+                ..Default::default()
+            }),
+            parse_params(
+                [os("diff"), os("--tabsize=42"), os("foo"), os("bar")]
+                    .iter()
+                    .cloned()
+                    .peekable()
+            )
+        );
+        assert_eq!(
+            Ok(Params {
+                executable: os("diff"),
+                from: os("foo"),
+                to: os("bar"),
+                tabsize: 42,
+                ..Default::default()
+            }),
+            parse_params(
+                [os("diff"), os("--tabsize"), os("42"), os("foo"), os("bar")]
+                    .iter()
+                    .cloned()
+                    .peekable()
+            )
+        );
+        assert!(parse_params(
+            [os("diff"), os("--tabsize=0"), os("foo"), os("bar")]
+                .iter()
+                .cloned()
+                .peekable()
+        )
+        .is_err());
+        assert!(parse_params(
+            [os("diff"), os("--tabsize"), os("foo"), os("bar")]
+                .iter()
+                .cloned()
+                .peekable()
+        )
+        .is_err());
+        assert!(parse_params(
+            [os("diff"), os("--tabsize="), os("foo"), os("bar")]
+                .iter()
+                .cloned()
+                .peekable()
+        )
+        .is_err());
+        assert!(parse_params(
+            [os("diff"), os("--tabsize=r2"), os("foo"), os("bar")]
+                .iter()
+                .cloned()
+                .peekable()
+        )
+        .is_err());
+        assert!(parse_params(
             [os("diff"), os("--tabsize=-1"), os("foo"), os("bar")]
                 .iter()
                 .cloned()
@@ -795,6 +2055,16 @@ mod tests {
         );
     }
     #[test]
+    fn extra_operand_after_double_dash() {
+        assert!(parse_params(
+            [os("diff"), os("--"), os("foo"), os("bar"), os("baz")]
+                .iter()
+                .cloned()
+                .peekable()
+        )
+        .is_err());
+    }
+    #[test]
     fn default_to_stdin() {
         assert_eq!(
             Ok(Params {
@@ -0,0 +1,156 @@
+// This file is part of the uutils diffutils package.
+//
+// For the full copyright and license information, please view the LICENSE-*
+// files that was distributed with this source code.
+
+use std::io::Write;
+
+use crate::params::Params;
+use crate::utils::diff_lines;
+use crate::utils::do_write_line;
+
+#[derive(Debug, PartialEq)]
+struct Mismatch<'a> {
+    pub line_number_expected: usize,
+    pub expected_count: usize,
+    pub actual: Vec<&'a [u8]>,
+}
+
+impl<'a> Mismatch<'a> {
+    fn new(line_number_expected: usize) -> Mismatch<'a> {
+        Mismatch {
+            line_number_expected,
+            expected_count: 0,
+            actual: Vec::new(),
+        }
+    }
+}
+
+// Produces a diff between the expected output and actual output.
+// Lines keep their terminating newline, so that a last line lacking one
+// differs from the same text with a newline, as in GNU diff -n.
+fn make_diff<'a>(
+    expected: &'a [u8],
+    actual: &'a [u8],
+    stop_early: bool,
+    params: &Params,
+) -> Vec<Mismatch<'a>> {
+    let mut line_number_expected = 1;
+    let mut results = Vec::new();
+    let mut mismatch = Mismatch::new(line_number_expected);
+
+    let expected_lines: Vec<&[u8]> = expected.split_inclusive(|&c| c == b'\n').collect();
+    let actual_lines: Vec<&[u8]> = actual.split_inclusive(|&c| c == b'\n').collect();
+
+    for result in diff_lines(&expected_lines, &actual_lines, params) {
+        match result {
+            diff::Result::Left(_) => {
+                if !mismatch.actual.is_empty() {
+                    results.push(mismatch);
+                    mismatch = Mismatch::new(line_number_expected);
+                }
+                mismatch.expected_count += 1;
+                line_number_expected += 1;
+            }
+            diff::Result::Right(str) => {
+                mismatch.actual.push(str);
+            }
+            diff::Result::Both(_, _) => {
+                line_number_expected += 1;
+                if !mismatch.actual.is_empty() || mismatch.expected_count != 0 {
+                    results.push(mismatch);
+                }
+                mismatch = Mismatch::new(line_number_expected);
+            }
+        }
+        if stop_early && !results.is_empty() {
+            // Optimization: stop analyzing the files as soon as there are any differences
+            return results;
+        }
+    }
+
+    if !mismatch.actual.is_empty() || mismatch.expected_count != 0 {
+        results.push(mismatch);
+    }
+
+    results
+}
+
+/// Diff two buffers and return the output in the RCS format of `diff -n`.
+///
+/// Each change is a `dL N` command deleting `N` lines from line `L` of the
+/// original, and/or an `aL N` command followed by `N` lines to insert after
+/// line `L` of the original.
+#[must_use]
+pub fn diff(expected: &[u8], actual: &[u8], params: &Params) -> Vec<u8> {
+    let mut output = Vec::new();
+    let diff_results = make_diff(expected, actual, params.brief, params);
+    if params.brief && !diff_results.is_empty() {
+        write!(&mut output, "\0").unwrap();
+        return output;
+    }
+    for result in diff_results {
+        if result.expected_count != 0 {
+            writeln!(
+                &mut output,
+                "d{} {}",
+                result.line_number_expected, result.expected_count
+            )
+            .unwrap();
+        }
+        if !result.actual.is_empty() {
+            writeln!(
+                &mut output,
+                "a{} {}",
+                result.line_number_expected + result.expected_count - 1,
+                result.actual.len()
+            )
+            .unwrap();
+            for actual in &result.actual {
+                // A last line without a newline is written as is
+                let (line, newline) = match actual.strip_suffix(b"\n") {
+                    Some(line) => (line, true),
+                    None => (*actual, false),
+                };
+                do_write_line(&mut output, line, params.expand_tabs, params.tabsize).unwrap();
+                if newline {
+                    writeln!(&mut output).unwrap();
+                }
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_basic() {
+        let diff = diff(b"a\nb\nc\nd\n", b"a\nB\nc\nx\ny\nd\n", &Params::default());
+        let expected = ["d2 1", "a2 1", "B", "a3 2", "x", "y", ""].join("\n");
+        assert_eq!(diff, expected.as_bytes());
+    }
+
+    #[test]
+    fn test_first_line() {
+        let params = Params::default();
+        assert_eq!(diff(b"a\nb\nc\n", b"b\nc\n", &params), b"d1 1\n");
+        assert_eq!(diff(b"b\nc\n", b"a\nb\nc\n", &params), b"a0 1\na\n");
+    }
+
+    #[test]
+    fn test_missing_newline() {
+        let params = Params::default();
+        assert_eq!(diff(b"a\nb", b"a\nc", &params), b"d2 1\na2 1\nc");
+        // "b" and "b\n" are different lines
+        assert_eq!(diff(b"a\nb\nc\nd\n", b"a\nb", &params), b"d2 3\na4 1\nb");
+    }
+
+    #[test]
+    fn test_identical() {
+        assert!(diff(b"a\nb\n", b"a\nb\n", &Params::default()).is_empty());
+    }
+}
@@ -4,29 +4,36 @@
 // files that was distributed with this source code.
 
 use std::collections::VecDeque;
-use std::io::Write;
+use std::ffi::OsString;
+use std::io::{self, Write};
 
 use crate::params::Params;
+use crate::utils::diff_lines;
 use crate::utils::do_write_line;
-use crate::utils::get_modification_time;
+use crate::utils::find_function_context;
+use crate::utils::set_color;
+use crate::utils::sha1::git_blob_id;
+use crate::utils::write_line_marker;
+use crate::utils::{from_file_header, header_file_name, is_new_from_file, to_file_header};
 
+// Lines borrow from the input buffers, so that large inputs are not copied.
 #[derive(Debug, PartialEq)]
-pub enum DiffLine {
-    Context(Vec<u8>),
-    Expected(Vec<u8>),
-    Actual(Vec<u8>),
+pub enum DiffLine<'a> {
+    Context(&'a [u8]),
+    Expected(&'a [u8]),
+    Actual(&'a [u8]),
     MissingNL,
 }
 
 #[derive(Debug, PartialEq)]
-struct Mismatch {
+struct Mismatch<'a> {
     pub line_number_expected: u32,
     pub line_number_actual: u32,
-    pub lines: Vec<DiffLine>,
+    pub lines: Vec<DiffLine<'a>>,
 }
 
-impl Mismatch {
-    fn new(line_number_expected: u32, line_number_actual: u32) -> Mismatch {
+impl<'a> Mismatch<'a> {
+    fn new(line_number_expected: u32, line_number_actual: u32) -> Mismatch<'a> {
         Mismatch {
             line_number_expected,
             line_number_actual,
@@ -36,16 +43,30 @@ impl Mismatch {
 }
 
 // Produces a diff between the expected output and actual output.
-fn make_diff(
-    expected: &[u8],
-    actual: &[u8],
+fn make_diff<'a>(
+    expected: &'a [u8],
+    actual: &'a [u8],
     context_size: usize,
     stop_early: bool,
-) -> Vec<Mismatch> {
+    params: &Params,
+) -> Vec<Mismatch<'a>> {
     let mut line_number_expected = 1;
     let mut line_number_actual = 1;
-    let mut context_queue: VecDeque<&[u8]> = VecDeque::with_capacity(context_size);
-    let mut lines_since_mismatch = context_size + 1;
+    // As in GNU diff, changes at most 2 * context_size unchanged lines apart
+    // share a hunk, so that the context of one runs into that of the other.
+    // Under -U auto, changes up to AUTO_CONTEXT_MERGE_GAP apart do too, with
+    // all the lines between them, while other hunks keep context_size.
+    let merge_gap = if params.context_auto {
+        (2 * context_size).max(AUTO_CONTEXT_MERGE_GAP)
+    } else {
+        2 * context_size
+    };
+    // Unchanged lines after the trailing context of a hunk, which lead into
+    // the next change: all of them if it shares the hunk, else the last
+    // context_size.
+    let queue_size = context_size.max(merge_gap - context_size);
+    let mut context_queue: VecDeque<&[u8]> = VecDeque::with_capacity(queue_size);
+    let mut lines_since_mismatch = merge_gap + 1;
     let mut results = Vec::new();
     let mut mismatch = Mismatch::new(0, 0);
 
@@ -65,11 +86,12 @@ fn make_diff(
         actual_lines.pop();
     }
 
-    for result in diff::slice(&expected_lines, &actual_lines) {
+    for result in diff_lines(&expected_lines, &actual_lines, params) {
         match result {
             diff::Result::Left(str) => {
-                if lines_since_mismatch >= context_size && lines_since_mismatch > 0 {
+                if lines_since_mismatch > merge_gap {
                     results.push(mismatch);
+                    context_queue.drain(..context_queue.len().saturating_sub(context_size));
                     mismatch = Mismatch::new(
                         line_number_expected - context_queue.len() as u32,
                         line_number_actual - context_queue.len() as u32,
@@ -77,7 +99,7 @@ fn make_diff(
                 }
 
                 while let Some(line) = context_queue.pop_front() {
-                    mismatch.lines.push(DiffLine::Context(line.to_vec()));
+                    mismatch.lines.push(DiffLine::Context(line));
                 }
 
                 if mismatch.lines.last() == Some(&DiffLine::MissingNL) {
@@ -86,7 +108,7 @@ fn make_diff(
                         Some(DiffLine::Actual(res)) => {
                             // We have to make sure that Actual (the + lines)
                             // always come after Expected (the - lines)
-                            mismatch.lines.push(DiffLine::Expected(str.to_vec()));
+                            mismatch.lines.push(DiffLine::Expected(str));
                             if line_number_expected > expected_lines_count {
                                 mismatch.lines.push(DiffLine::MissingNL);
                             }
@@ -96,7 +118,7 @@ fn make_diff(
                         _ => unreachable!("unterminated Left and Common lines shouldn't be followed by more Left lines"),
                     }
                 } else {
-                    mismatch.lines.push(DiffLine::Expected(str.to_vec()));
+                    mismatch.lines.push(DiffLine::Expected(str));
                     if line_number_expected > expected_lines_count {
                         mismatch.lines.push(DiffLine::MissingNL);
                     }
@@ -105,8 +127,9 @@ fn make_diff(
                 lines_since_mismatch = 0;
             }
             diff::Result::Right(str) => {
-                if lines_since_mismatch >= context_size && lines_since_mismatch > 0 {
+                if lines_since_mismatch > merge_gap {
                     results.push(mismatch);
+                    context_queue.drain(..context_queue.len().saturating_sub(context_size));
                     mismatch = Mismatch::new(
                         line_number_expected - context_queue.len() as u32,
                         line_number_actual - context_queue.len() as u32,
@@ -115,10 +138,10 @@ fn make_diff(
 
                 while let Some(line) = context_queue.pop_front() {
                     debug_assert!(mismatch.lines.last() != Some(&DiffLine::MissingNL));
-                    mismatch.lines.push(DiffLine::Context(line.to_vec()));
+                    mismatch.lines.push(DiffLine::Context(line));
                 }
 
-                mismatch.lines.push(DiffLine::Actual(str.to_vec()));
+                mismatch.lines.push(DiffLine::Actual(str));
                 if line_number_actual > actual_lines_count {
                     mismatch.lines.push(DiffLine::MissingNL);
                 }
@@ -126,24 +149,25 @@ fn make_diff(
                 lines_since_mismatch = 0;
             }
             diff::Result::Both(str, _) => {
-                // if one of them is missing a newline and the other isn't, then they don't actually match
-                if (line_number_actual > actual_lines_count)
-                    && (line_number_expected > expected_lines_count)
+                let expected_missing_nl = line_number_expected > expected_lines_count;
+                let actual_missing_nl = line_number_actual > actual_lines_count;
+                // if one of them is missing a newline and the other isn't, then they don't actually match,
+                // unless -Z counts the newline as trailing white space
+                if expected_missing_nl && actual_missing_nl
+                    || params.ignore_trailing_space && (expected_missing_nl || actual_missing_nl)
                 {
-                    if context_queue.len() < context_size {
-                        while let Some(line) = context_queue.pop_front() {
-                            debug_assert!(mismatch.lines.last() != Some(&DiffLine::MissingNL));
-                            mismatch.lines.push(DiffLine::Context(line.to_vec()));
-                        }
-                        if lines_since_mismatch < context_size {
-                            mismatch.lines.push(DiffLine::Context(str.to_vec()));
+                    // The last line: part of the trailing context, if any
+                    if lines_since_mismatch < context_size {
+                        mismatch.lines.push(DiffLine::Context(str));
+                        if expected_missing_nl {
                             mismatch.lines.push(DiffLine::MissingNL);
                         }
                     }
-                    lines_since_mismatch = 0;
-                } else if line_number_actual > actual_lines_count {
-                    if lines_since_mismatch >= context_size && lines_since_mismatch > 0 {
+                    lines_since_mismatch += 1;
+                } else if actual_missing_nl {
+                    if lines_since_mismatch > merge_gap {
                         results.push(mismatch);
+                        context_queue.drain(..context_queue.len().saturating_sub(context_size));
                         mismatch = Mismatch::new(
                             line_number_expected - context_queue.len() as u32,
                             line_number_actual - context_queue.len() as u32,
@@ -151,15 +175,16 @@ fn make_diff(
                     }
                     while let Some(line) = context_queue.pop_front() {
                         debug_assert!(mismatch.lines.last() != Some(&DiffLine::MissingNL));
-                        mismatch.lines.push(DiffLine::Context(line.to_vec()));
+                        mismatch.lines.push(DiffLine::Context(line));
                     }
-                    mismatch.lines.push(DiffLine::Expected(str.to_vec()));
-                    mismatch.lines.push(DiffLine::Actual(str.to_vec()));
+                    mismatch.lines.push(DiffLine::Expected(str));
+                    mismatch.lines.push(DiffLine::Actual(str));
                     mismatch.lines.push(DiffLine::MissingNL);
                     lines_since_mismatch = 0;
-                } else if line_number_expected > expected_lines_count {
-                    if lines_since_mismatch >= context_size && lines_since_mismatch > 0 {
+                } else if expected_missing_nl {
+                    if lines_since_mismatch > merge_gap {
                         results.push(mismatch);
+                        context_queue.drain(..context_queue.len().saturating_sub(context_size));
                         mismatch = Mismatch::new(
                             line_number_expected - context_queue.len() as u32,
                             line_number_actual - context_queue.len() as u32,
@@ -167,20 +192,20 @@ fn make_diff(
                     }
                     while let Some(line) = context_queue.pop_front() {
                         debug_assert!(mismatch.lines.last() != Some(&DiffLine::MissingNL));
-                        mismatch.lines.push(DiffLine::Context(line.to_vec()));
+                        mismatch.lines.push(DiffLine::Context(line));
                     }
-                    mismatch.lines.push(DiffLine::Expected(str.to_vec()));
+                    mismatch.lines.push(DiffLine::Expected(str));
                     mismatch.lines.push(DiffLine::MissingNL);
-                    mismatch.lines.push(DiffLine::Actual(str.to_vec()));
+                    mismatch.lines.push(DiffLine::Actual(str));
                     lines_since_mismatch = 0;
                 } else {
-                    debug_assert!(context_queue.len() <= context_size);
-                    if context_queue.len() >= context_size {
+                    debug_assert!(context_queue.len() <= queue_size);
+                    if context_queue.len() >= queue_size {
                         let _ = context_queue.pop_front();
                     }
                     if lines_since_mismatch < context_size {
-                        mismatch.lines.push(DiffLine::Context(str.to_vec()));
-                    } else if context_size > 0 {
+                        mismatch.lines.push(DiffLine::Context(str));
+                    } else if queue_size > 0 {
                         context_queue.push_back(str);
                     }
                     lines_since_mismatch += 1;
@@ -198,36 +223,35 @@ fn make_diff(
     results.push(mismatch);
     results.remove(0);
 
-    if results.is_empty() && expected_lines_count != actual_lines_count {
+    if results.is_empty()
+        && expected_lines_count != actual_lines_count
+        && !params.ignore_trailing_space
+    {
         let mut mismatch = Mismatch::new(expected_lines.len() as u32, actual_lines.len() as u32);
         // empty diff and only expected lines has a missing line at end
         if expected_lines_count != expected_lines.len() as u32 {
             mismatch.lines.push(DiffLine::Expected(
                 expected_lines
                     .pop()
-                    .expect("can't be empty; produced by split()")
-                    .to_vec(),
+                    .expect("can't be empty; produced by split()"),
             ));
             mismatch.lines.push(DiffLine::MissingNL);
             mismatch.lines.push(DiffLine::Actual(
                 actual_lines
                     .pop()
-                    .expect("can't be empty; produced by split()")
-                    .to_vec(),
+                    .expect("can't be empty; produced by split()"),
             ));
             results.push(mismatch);
         } else if actual_lines_count != actual_lines.len() as u32 {
             mismatch.lines.push(DiffLine::Expected(
                 expected_lines
                     .pop()
-                    .expect("can't be empty; produced by split()")
-                    .to_vec(),
+                    .expect("can't be empty; produced by split()"),
             ));
             mismatch.lines.push(DiffLine::Actual(
                 actual_lines
                     .pop()
-                    .expect("can't be empty; produced by split()")
-                    .to_vec(),
+                    .expect("can't be empty; produced by split()"),
             ));
             mismatch.lines.push(DiffLine::MissingNL);
             results.push(mismatch);
@@ -237,28 +261,21 @@ fn make_diff(
     results
 }
 
-#[must_use]
-pub fn diff(expected: &[u8], actual: &[u8], params: &Params) -> Vec<u8> {
-    let from_modified_time = get_modification_time(&params.from.to_string_lossy());
-    let to_modified_time = get_modification_time(&params.to.to_string_lossy());
-    let mut output = format!(
-        "--- {0}\t{1}\n+++ {2}\t{3}\n",
-        params.from.to_string_lossy(),
-        from_modified_time,
-        params.to.to_string_lossy(),
-        to_modified_time
-    )
-    .into_bytes();
-    let diff_results = make_diff(expected, actual, params.context_count, params.brief);
-    if diff_results.is_empty() {
-        return Vec::new();
-    }
-    if params.brief {
-        return output;
-    }
-    for result in diff_results {
-        let mut line_number_expected = result.line_number_expected;
-        let mut line_number_actual = result.line_number_actual;
+/// A hunk of a unified diff: the ranges shown in its `@@ -a,b +c,d @@`
+/// header and the lines it is made of.
+#[derive(Debug, PartialEq)]
+pub struct Hunk<'a> {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<DiffLine<'a>>,
+}
+
+impl<'a> From<Mismatch<'a>> for Hunk<'a> {
+    fn from(result: Mismatch<'a>) -> Hunk<'a> {
+        let mut line_number_expected = result.line_number_expected as usize;
+        let mut line_number_actual = result.line_number_actual as usize;
         let mut expected_count = 0;
         let mut actual_count = 0;
         for line in &result.lines {
@@ -358,51 +375,225 @@ pub fn diff(expected: &[u8], actual: &[u8], params: &Params) -> Vec<u8> {
         if actual_count == 0 {
             line_number_actual -= 1;
         }
-        let exp_ct = if expected_count == 1 {
+        Hunk {
+            old_start: line_number_expected,
+            old_lines: expected_count,
+            new_start: line_number_actual,
+            new_lines: actual_count,
+            lines: result.lines,
+        }
+    }
+}
+
+/// Under `-U auto`, runs of at most this many unchanged lines between two
+/// changes are shown in full rather than splitting the changes into
+/// separate hunks.
+const AUTO_CONTEXT_MERGE_GAP: usize = 16;
+
+/// Compute the hunks of a unified diff without formatting them. The context
+/// size and the way lines are compared are taken from `params`.
+#[must_use]
+pub fn unified_hunks<'a>(expected: &'a [u8], actual: &'a [u8], params: &Params) -> Vec<Hunk<'a>> {
+    make_diff(expected, actual, params.context_count, false, params)
+        .into_iter()
+        .map(Hunk::from)
+        .collect()
+}
+
+#[must_use]
+pub fn diff(expected: &[u8], actual: &[u8], params: &Params) -> Vec<u8> {
+    let mut output = Vec::new();
+    diff_to_writer(expected, actual, params, &mut output).expect("write to Vec is infallible");
+    output
+}
+
+/// Like [`diff`], but write each hunk to `output` as soon as it is formatted
+/// rather than collecting the whole diff in memory. Returns whether the
+/// inputs differ.
+pub fn diff_to_writer<W: Write>(
+    expected: &[u8],
+    actual: &[u8],
+    params: &Params,
+    output: &mut W,
+) -> io::Result<bool> {
+    if params.brief {
+        if make_diff(expected, actual, params.context_count, true, params).is_empty() {
+            return Ok(false);
+        }
+        write_header(output, expected, actual, params)?;
+        return Ok(true);
+    }
+    let hunks = unified_hunks(expected, actual, params);
+    if hunks.is_empty() {
+        return Ok(false);
+    }
+    write_header(output, expected, actual, params)?;
+    let expected_lines: Vec<&[u8]> = if params.show_c_function {
+        expected.split(|&c| c == b'\n').collect()
+    } else {
+        Vec::new()
+    };
+    // With -T, a tab is inserted between the indicator and the line, and
+    // takes the place of the blank indicator of context lines, as in GNU diff
+    let separator = if params.initial_tab { "\t" } else { "" };
+    let context_marker = if params.initial_tab { "" } else { " " };
+    for hunk in hunks {
+        let exp_ct = if hunk.old_lines == 1 {
             String::new()
         } else {
-            format!(",{expected_count}")
+            format!(",{}", hunk.old_lines)
         };
-        let act_ct = if actual_count == 1 {
+        let act_ct = if hunk.new_lines == 1 {
             String::new()
         } else {
-            format!(",{actual_count}")
+            format!(",{}", hunk.new_lines)
         };
-        writeln!(
+        set_color(output, params.color, &params.palette.line_number)?;
+        write!(
             output,
-            "@@ -{line_number_expected}{exp_ct} +{line_number_actual}{act_ct} @@"
-        )
-        .expect("write to Vec is infallible");
-        for line in result.lines {
+            "@@ -{}{exp_ct} +{}{act_ct} @@",
+            hunk.old_start, hunk.new_start
+        )?;
+        set_color(output, params.color, &params.palette.reset)?;
+        if params.show_c_function {
+            // Lines before the first one of the hunk, which for a pure
+            // insertion is the line old_start points at.
+            let count = if hunk.old_lines == 0 {
+                hunk.old_start
+            } else {
+                hunk.old_start - 1
+            };
+            if let Some(function) = find_function_context(&expected_lines, count) {
+                write!(output, " ")?;
+                set_color(output, params.color, FUNCTION_COLOR)?;
+                output.write_all(function)?;
+                set_color(output, params.color, &params.palette.reset)?;
+            }
+        }
+        writeln!(output)?;
+        for line in hunk.lines {
             match line {
                 DiffLine::Expected(e) => {
-                    write!(output, "-").expect("write to Vec is infallible");
-                    do_write_line(&mut output, &e, params.expand_tabs, params.tabsize)
-                        .expect("write to Vec is infallible");
-                    writeln!(output).unwrap();
+                    set_color(output, params.color, &params.palette.delete)?;
+                    write_line_marker(output, "-", separator, e, params)?;
+                    do_write_line(output, e, params.expand_tabs, params.tabsize)?;
+                    set_color(output, params.color, &params.palette.reset)?;
+                    writeln!(output)?;
                 }
                 DiffLine::Context(c) => {
-                    write!(output, " ").expect("write to Vec is infallible");
-                    do_write_line(&mut output, &c, params.expand_tabs, params.tabsize)
-                        .expect("write to Vec is infallible");
-                    writeln!(output).unwrap();
+                    write_line_marker(output, context_marker, separator, c, params)?;
+                    do_write_line(output, c, params.expand_tabs, params.tabsize)?;
+                    writeln!(output)?;
                 }
                 DiffLine::Actual(r) => {
-                    write!(output, "+",).expect("write to Vec is infallible");
-                    do_write_line(&mut output, &r, params.expand_tabs, params.tabsize)
-                        .expect("write to Vec is infallible");
-                    writeln!(output).unwrap();
+                    set_color(output, params.color, &params.palette.add)?;
+                    write_line_marker(output, "+", separator, r, params)?;
+                    do_write_line(output, r, params.expand_tabs, params.tabsize)?;
+                    set_color(output, params.color, &params.palette.reset)?;
+                    writeln!(output)?;
                 }
                 DiffLine::MissingNL => {
-                    writeln!(output, r"\ No newline at end of file")
-                        .expect("write to Vec is infallible");
+                    writeln!(output, r"\ No newline at end of file")?;
                 }
             }
         }
     }
-    output
+    Ok(true)
+}
+
+fn write_header<W: Write>(
+    output: &mut W,
+    expected: &[u8],
+    actual: &[u8],
+    params: &Params,
+) -> io::Result<()> {
+    if params.git {
+        return write_git_header(output, expected, actual, params);
+    }
+    write_header_line(output, params, &[b"--- ", &from_file_header(params)])?;
+    write_header_line(output, params, &[b"+++ ", &to_file_header(params)])
+}
+
+// Write one line of the file header. Under --color the whole line, file name
+// and timestamp alike, is a single bold span, as in git.
+fn write_header_line<W: Write>(output: &mut W, params: &Params, parts: &[&[u8]]) -> io::Result<()> {
+    set_color(output, params.color, &params.palette.header)?;
+    for part in parts {
+        output.write_all(part)?;
+    }
+    set_color(output, params.color, &params.palette.reset)?;
+    writeln!(output)
+}
+
+// The headers of `git diff`, which `git apply` expects: file names are
+// prefixed with a/ and b/ instead of being followed by a timestamp, and an
+// index line names both versions by their abbreviated blob ids. A --label
+// replaces the file name after the prefix.
+fn write_git_header<W: Write>(
+    output: &mut W,
+    expected: &[u8],
+    actual: &[u8],
+    params: &Params,
+) -> io::Result<()> {
+    let name = |label: &Option<OsString>, file: &OsString| {
+        let mut name = match label {
+            Some(label) => label.as_encoded_bytes().to_vec(),
+            None => header_file_name(file, params),
+        };
+        // Like git, show an absolute path under the prefix as if relative
+        let slashes = name.iter().take_while(|&&c| c == b'/').count();
+        name.drain(..slashes);
+        name
+    };
+    let from = [b"a/", &name(&params.from_label, &params.from)[..]].concat();
+    let to = [b"b/", &name(&params.to_label, &params.to)[..]].concat();
+    let new_file = is_new_from_file(params);
+    write_header_line(output, params, &[b"diff --git ", &from, b" ", &to])?;
+    if new_file {
+        let mode = format!("new file mode {}", git_mode(&params.to));
+        write_header_line(output, params, &[mode.as_bytes()])?;
+        let index = format!("index 0000000..{:.7}", git_blob_id(actual));
+        write_header_line(output, params, &[index.as_bytes()])?;
+    } else {
+        let (old_mode, new_mode) = (git_mode(&params.from), git_mode(&params.to));
+        if old_mode != new_mode {
+            write_header_line(output, params, &[b"old mode ", old_mode.as_bytes()])?;
+            write_header_line(output, params, &[b"new mode ", new_mode.as_bytes()])?;
+        }
+        let mut index = format!(
+            "index {:.7}..{:.7}",
+            git_blob_id(expected),
+            git_blob_id(actual)
+        );
+        if old_mode == new_mode {
+            index = format!("{index} {new_mode}");
+        }
+        write_header_line(output, params, &[index.as_bytes()])?;
+    }
+    let from: &[u8] = if new_file { b"/dev/null" } else { &from };
+    write_header_line(output, params, &[b"--- ", from])?;
+    write_header_line(output, params, &[b"+++ ", &to])
 }
 
+// The git mode of a regular file: executable or not.
+fn git_mode(path: &OsString) -> &'static str {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if std::fs::metadata(path).is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
+        {
+            return "100755";
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    "100644"
+}
+
+// The SGR code of the -p function context, which --color dims to set it
+// apart from the hunk header. The other codes come from params.palette.
+const FUNCTION_COLOR: &str = "2";
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -942,4 +1133,606 @@ mod tests {
         );
         assert!(nodiff_brief.is_empty());
     }
+
+    #[test]
+    fn test_initial_tab() {
+        use crate::assert_diff_eq;
+
+        let from = ["a", "b", "c"].join("\n");
+        let to = ["a", "d", "c"].join("\n");
+
+        let check = |initial_tab: bool| {
+            let diff = diff(
+                from.as_bytes(),
+                to.as_bytes(),
+                &Params {
+                    from: "foo".into(),
+                    to: "bar".into(),
+                    initial_tab,
+                    ..Default::default()
+                },
+            );
+            let (context, sep) = if initial_tab { ("\t", "\t") } else { (" ", "") };
+            let expected = [
+                "--- foo\tTIMESTAMP",
+                "+++ bar\tTIMESTAMP",
+                "@@ -1,3 +1,3 @@",
+                &format!("{context}a"),
+                &format!("-{sep}b"),
+                &format!("+{sep}d"),
+                &format!("{context}c"),
+                r"\ No newline at end of file",
+                "",
+            ]
+            .join("\n");
+            assert_diff_eq!(diff, expected);
+        };
+        check(false);
+        check(true);
+    }
+
+    #[test]
+    fn test_suppress_blank_empty() {
+        let from = b"a\n\nb\n\n";
+        let to = b"a\n\nc\n";
+        let body = |suppress_blank_empty: bool| -> String {
+            let params = Params {
+                from: "foo".into(),
+                to: "bar".into(),
+                suppress_blank_empty,
+                ..Default::default()
+            };
+            String::from_utf8(diff(from, to, &params))
+                .unwrap()
+                .split_inclusive('\n')
+                .skip(3)
+                .collect()
+        };
+        assert_eq!(body(false), " a\n \n-b\n-\n+c\n");
+        // Empty lines lose the space after the marker, and context lines the
+        // marker too
+        assert_eq!(body(true), " a\n\n-b\n-\n+c\n");
+    }
+
+    #[test]
+    fn test_git_header() {
+        let from = b"a\nb\nc\n";
+        let to = b"a\nd\nc\n";
+        let params = Params {
+            from: "foo".into(),
+            to: "bar".into(),
+            git: true,
+            ..Default::default()
+        };
+        let expected = [
+            "diff --git a/foo b/bar",
+            "index de98044..3171744 100644",
+            "--- a/foo",
+            "+++ b/bar",
+            "@@ -1,3 +1,3 @@",
+            " a",
+            "-b",
+            "+d",
+            " c",
+            "",
+        ]
+        .join("\n");
+        assert_eq!(
+            String::from_utf8(diff(from, to, &params)).unwrap(),
+            expected
+        );
+
+        // Labels replace the file names after the prefixes
+        let params = Params {
+            from_label: Some("old/foo".into()),
+            to_label: Some("new/foo".into()),
+            ..params
+        };
+        let output = diff(from, to, &params);
+        assert!(output.starts_with(
+            b"diff --git a/old/foo b/new/foo\nindex de98044..3171744 100644\n--- a/old/foo\n+++ b/new/foo\n"
+        ));
+
+        // No header at all without differences
+        assert!(diff(from, from, &params).is_empty());
+
+        // Absolute paths lose their leading slash after the prefixes
+        let params = Params {
+            from: "/nonexistent/foo".into(),
+            to: "/nonexistent/bar".into(),
+            from_label: None,
+            to_label: None,
+            ..params
+        };
+        let output = diff(from, to, &params);
+        assert!(output.starts_with(
+            b"diff --git a/nonexistent/foo b/nonexistent/bar\nindex de98044..3171744 100644\n--- a/nonexistent/foo\n+++ b/nonexistent/bar\n"
+        ));
+    }
+
+    #[test]
+    fn test_color_header() {
+        // Each header line is a single bold span, file name and timestamp or
+        // git metadata alike
+        let from = b"a\nb\nc\n";
+        let to = b"a\nd\nc\n";
+        let params = Params {
+            from: "Cargo.toml".into(),
+            to: "Cargo.toml".into(),
+            color: true,
+            ..Default::default()
+        };
+        let output = String::from_utf8(diff(from, to, &params)).unwrap();
+        let header: Vec<&str> = output.lines().take(2).collect();
+        for (line, prefix) in header.iter().zip(["\x1b[1m--- ", "\x1b[1m+++ "]) {
+            assert!(line.starts_with(prefix), "{line:?}");
+            assert!(line.ends_with("\x1b[0m"), "{line:?}");
+            assert_eq!(line.matches('\x1b').count(), 2, "{line:?}");
+            assert!(line.contains("Cargo.toml\t"), "{line:?}");
+        }
+
+        let params = Params {
+            from: "foo".into(),
+            to: "bar".into(),
+            git: true,
+            ..params
+        };
+        let output = String::from_utf8(diff(from, to, &params)).unwrap();
+        assert_eq!(
+            output.lines().take(5).collect::<Vec<_>>(),
+            [
+                "\x1b[1mdiff --git a/foo b/bar\x1b[0m",
+                "\x1b[1mindex de98044..3171744 100644\x1b[0m",
+                "\x1b[1m--- a/foo\x1b[0m",
+                "\x1b[1m+++ b/bar\x1b[0m",
+                "\x1b[36m@@ -1,3 +1,3 @@\x1b[0m",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ignore_trailing_space() {
+        let params = Params {
+            from: "foo".into(),
+            to: "bar".into(),
+            ignore_trailing_space: true,
+            ..Default::default()
+        };
+        assert!(diff(b"a  \n", b"a\n", &params).is_empty());
+        assert!(diff(b"a\t\r\n", b"a\n", &params).is_empty());
+        assert!(!diff(b" a\n", b"a\n", &params).is_empty());
+        assert!(!diff(b"a b\n", b"ab\n", &params).is_empty());
+        // The missing newline counts as trailing white space too
+        assert!(diff(b"a  ", b"a\n", &params).is_empty());
+
+        // Lines are shown as they are in the inputs, context as in the from-file
+        let hunks = unified_hunks(b"x \ny\n", b"x\nz\t\n", &params);
+        assert_eq!(
+            hunks[0].lines,
+            [
+                DiffLine::Context(b"x "),
+                DiffLine::Expected(b"y"),
+                DiffLine::Actual(b"z\t"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ignore_tab_expansion() {
+        let params = Params {
+            from: "foo".into(),
+            to: "bar".into(),
+            ignore_tab_expansion: true,
+            ..Default::default()
+        };
+        assert!(diff(b"\tx\n", b"        x\n", &params).is_empty());
+        assert!(diff(b"  \tx\n", b"        x\n", &params).is_empty());
+        assert!(diff(b"ab\tx\n", b"ab      x\n", &params).is_empty());
+        assert!(!diff(b"\tx\n", b"    x\n", &params).is_empty());
+        assert!(!diff(b"\tx \n", b"        x\n", &params).is_empty());
+        let params = Params {
+            tabsize: 4,
+            ..params
+        };
+        assert!(diff(b"\tx\n", b"    x\n", &params).is_empty());
+    }
+
+    #[test]
+    fn test_unified_hunks() {
+        let from = ["a", "b", "c", "d", "e", "f", "g", "h", ""].join("\n");
+        let to = ["a", "B", "c", "d", "e", "f", "g", "h", "i"].join("\n");
+
+        let params = Params {
+            context_count: 1,
+            ..Default::default()
+        };
+        let hunks = unified_hunks(from.as_bytes(), to.as_bytes(), &params);
+        assert_eq!(
+            hunks,
+            vec![
+                Hunk {
+                    old_start: 1,
+                    old_lines: 3,
+                    new_start: 1,
+                    new_lines: 3,
+                    lines: vec![
+                        DiffLine::Context(b"a"),
+                        DiffLine::Expected(b"b"),
+                        DiffLine::Actual(b"B"),
+                        DiffLine::Context(b"c"),
+                    ],
+                },
+                Hunk {
+                    old_start: 8,
+                    old_lines: 1,
+                    new_start: 8,
+                    new_lines: 2,
+                    lines: vec![
+                        DiffLine::Context(b"h"),
+                        DiffLine::Actual(b"i"),
+                        DiffLine::MissingNL,
+                    ],
+                },
+            ]
+        );
+
+        assert!(unified_hunks(from.as_bytes(), from.as_bytes(), &Params::default()).is_empty());
+    }
+
+    #[test]
+    fn test_show_c_function() {
+        use crate::assert_diff_eq;
+
+        let from = [
+            "int main() {",
+            "  a();",
+            "  b();",
+            "  c();",
+            "  d();",
+            "}",
+            "",
+        ]
+        .join("\n");
+        let to = [
+            "int main() {",
+            "  a();",
+            "  b();",
+            "  c();",
+            "  e();",
+            "}",
+            "",
+        ]
+        .join("\n");
+
+        let diff_plain = diff(
+            from.as_bytes(),
+            to.as_bytes(),
+            &Params {
+                from: "foo".into(),
+                to: "bar".into(),
+                show_c_function: true,
+                ..Default::default()
+            },
+        );
+        let expected_plain = [
+            "--- foo\tTIMESTAMP",
+            "+++ bar\tTIMESTAMP",
+            "@@ -2,5 +2,5 @@ int main() {",
+            "   a();",
+            "   b();",
+            "   c();",
+            "-  d();",
+            "+  e();",
+            " }",
+            "",
+        ]
+        .join("\n");
+        assert_diff_eq!(diff_plain, expected_plain);
+
+        let diff_color = diff(
+            from.as_bytes(),
+            to.as_bytes(),
+            &Params {
+                from: "foo".into(),
+                to: "bar".into(),
+                show_c_function: true,
+                color: true,
+                ..Default::default()
+            },
+        );
+        let expected_color = [
+            "\x1b[1m--- foo\tTIMESTAMP\x1b[0m",
+            "\x1b[1m+++ bar\tTIMESTAMP\x1b[0m",
+            "\x1b[36m@@ -2,5 +2,5 @@\x1b[0m \x1b[2mint main() {\x1b[0m",
+            "   a();",
+            "   b();",
+            "   c();",
+            "\x1b[31m-  d();\x1b[0m",
+            "\x1b[32m+  e();\x1b[0m",
+            " }",
+            "",
+        ]
+        .join("\n");
+        assert_diff_eq!(diff_color, expected_color);
+    }
+
+    #[test]
+    fn test_context_auto() {
+        // Changes on lines 2, 12 and 40: 9 and 27 unchanged lines apart
+        let from: String = (1..=50).map(|i| format!("{i}\n")).collect();
+        let to: String = (1..=50)
+            .map(|i| match i {
+                2 | 12 | 40 => format!("{i}x\n"),
+                _ => format!("{i}\n"),
+            })
+            .collect();
+        let hunk_headers = |params: &Params| -> Vec<String> {
+            let output = diff(from.as_bytes(), to.as_bytes(), params);
+            String::from_utf8(output)
+                .unwrap()
+                .lines()
+                .filter(|line| line.starts_with("@@"))
+                .map(str::to_string)
+                .collect()
+        };
+
+        let params = Params {
+            from: "foo".into(),
+            to: "bar".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            hunk_headers(&params),
+            ["@@ -1,5 +1,5 @@", "@@ -9,7 +9,7 @@", "@@ -37,7 +37,7 @@"]
+        );
+
+        // The nearby changes share a hunk, with all the lines between them,
+        // and the distant change keeps its own with the usual context.
+        let params = Params {
+            context_auto: true,
+            ..params
+        };
+        assert_eq!(
+            hunk_headers(&params),
+            ["@@ -1,15 +1,15 @@", "@@ -37,7 +37,7 @@"]
+        );
+
+        // Changes up to 16 unchanged lines apart are merged
+        for (gap, headers) in [
+            (16, vec!["@@ -2,24 +2,24 @@"]),
+            (17, vec!["@@ -2,7 +2,7 @@", "@@ -20,7 +20,7 @@"]),
+        ] {
+            let second = 5 + gap + 1;
+            let to: String = (1..=50)
+                .map(|i| match i {
+                    i if i == 5 || i == second => format!("{i}x\n"),
+                    _ => format!("{i}\n"),
+                })
+                .collect();
+            let output = diff(from.as_bytes(), to.as_bytes(), &params);
+            let output = String::from_utf8(output).unwrap();
+            let found: Vec<&str> = output.lines().filter(|l| l.starts_with("@@")).collect();
+            assert_eq!(found, headers, "gap {gap}");
+        }
+
+        // Widely spaced changes keep the default context. Name an existing
+        // file so that both runs print the same timestamp.
+        let to = from.replace("\n2\n", "\n2x\n").replace("\n30\n", "\n30x\n");
+        let params = Params {
+            from: "Cargo.toml".into(),
+            to: "Cargo.toml".into(),
+            ..params
+        };
+        let output = diff(from.as_bytes(), to.as_bytes(), &params);
+        let defaults = Params {
+            context_auto: false,
+            ..params
+        };
+        assert_eq!(output, diff(from.as_bytes(), to.as_bytes(), &defaults));
+    }
+
+    #[test]
+    fn test_hunk_merge_boundary() {
+        use std::fs::{self, File};
+        use std::process::Command;
+        let target = "target/unified-diff-merge/";
+        let _ = fs::create_dir(target);
+        // With 3 lines of context, changes separated by up to 6 unchanged
+        // lines share a hunk, as in GNU diff; 7 apart they do not.
+        for (gap, headers) in [
+            (5, vec!["@@ -3,13 +3,13 @@"]),
+            (6, vec!["@@ -3,14 +3,14 @@"]),
+            (7, vec!["@@ -3,7 +3,7 @@", "@@ -11,7 +11,7 @@"]),
+            (8, vec!["@@ -3,7 +3,7 @@", "@@ -12,7 +12,7 @@"]),
+        ] {
+            let from: String = (1..=30).map(|i| format!("{i}\n")).collect();
+            let to: String = (1..=30)
+                .map(|i| match i {
+                    6 => "6x\n".to_string(),
+                    i if i == 7 + gap => format!("{i}x\n"),
+                    _ => format!("{i}\n"),
+                })
+                .collect();
+            let alef = format!("{target}alef{gap}");
+            let diff = diff(
+                from.as_bytes(),
+                to.as_bytes(),
+                &Params {
+                    from: alef.clone().into(),
+                    to: alef.clone().into(),
+                    ..Default::default()
+                },
+            );
+            let output = String::from_utf8(diff.clone()).unwrap();
+            let hunk_headers: Vec<&str> = output
+                .lines()
+                .filter(|line| line.starts_with("@@"))
+                .collect();
+            assert_eq!(hunk_headers, headers, "gap {gap}");
+
+            fs::write(&alef, &from).unwrap();
+            fs::write(format!("{alef}.diff"), &diff).unwrap();
+            let output = Command::new("patch")
+                .arg("-p0")
+                .stdin(File::open(format!("{alef}.diff")).unwrap())
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{output:?}");
+            assert_eq!(fs::read_to_string(&alef).unwrap(), to);
+        }
+    }
+
+    #[test]
+    fn test_single_line_ranges() {
+        // A count of 1 is left out of the @@ header, other counts are not
+        let hunks = |from: &[u8], to: &[u8], context_count: usize| -> String {
+            let params = Params {
+                from: "foo".into(),
+                to: "bar".into(),
+                context_count,
+                ..Default::default()
+            };
+            let output = String::from_utf8(diff(from, to, &params)).unwrap();
+            output.split_inclusive('\n').skip(2).collect()
+        };
+        for (from, to, context_count, expected) in [
+            // change
+            (
+                &b"a\nb\nc\n"[..],
+                &b"a\nB\nc\n"[..],
+                0,
+                "@@ -2 +2 @@\n-b\n+B\n",
+            ),
+            (b"b\n", b"B\n", 1, "@@ -1 +1 @@\n-b\n+B\n"),
+            (
+                b"a\nb\nc\n",
+                b"a\nB\nc\n",
+                1,
+                "@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n",
+            ),
+            // insertion
+            (b"a\nb\nc\n", b"a\nb\nx\nc\n", 0, "@@ -2,0 +3 @@\n+x\n"),
+            (b"", b"x\n", 0, "@@ -0,0 +1 @@\n+x\n"),
+            (
+                b"a\nb\nc\n",
+                b"a\nb\nx\nc\n",
+                1,
+                "@@ -2,2 +2,3 @@\n b\n+x\n c\n",
+            ),
+            // deletion
+            (b"a\nb\nc\n", b"a\nc\n", 0, "@@ -2 +1,0 @@\n-b\n"),
+            (b"x\n", b"", 0, "@@ -1 +0,0 @@\n-x\n"),
+            (b"a\nb\nc\n", b"a\nc\n", 1, "@@ -1,3 +1,2 @@\n a\n-b\n c\n"),
+        ] {
+            assert_eq!(hunks(from, to, context_count), expected);
+        }
+    }
+
+    #[test]
+    fn test_diff_to_writer() {
+        let from = b"a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let to = b"a\nB\nc\nd\ne\nf\ng\nh\nI\nj";
+        // Name an existing file so that both runs print the same timestamp
+        for params in [
+            Params {
+                from: "Cargo.toml".into(),
+                to: "Cargo.toml".into(),
+                ..Default::default()
+            },
+            Params {
+                from: "Cargo.toml".into(),
+                to: "Cargo.toml".into(),
+                context_count: 1,
+                brief: true,
+                ..Default::default()
+            },
+        ] {
+            let mut output = Vec::new();
+            assert!(diff_to_writer(from, to, &params, &mut output).unwrap());
+            assert_eq!(output, diff(from, to, &params));
+
+            let mut output = Vec::new();
+            assert!(!diff_to_writer(from, from, &params, &mut output).unwrap());
+            assert!(output.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_color_empty_lines() {
+        use crate::assert_diff_eq;
+
+        let params = Params {
+            from: "foo".into(),
+            to: "bar".into(),
+            color: true,
+            ..Default::default()
+        };
+        let diff = diff(b"a\n\nb\n", b"a\nb\n\n", &params);
+        let expected = [
+            "\x1b[1m--- foo\tTIMESTAMP\x1b[0m",
+            "\x1b[1m+++ bar\tTIMESTAMP\x1b[0m",
+            "\x1b[36m@@ -1,3 +1,3 @@\x1b[0m",
+            " a",
+            "\x1b[31m-\x1b[0m",
+            " b",
+            "\x1b[32m+\x1b[0m",
+            "",
+        ]
+        .join("\n");
+        assert_diff_eq!(diff, expected);
+    }
+
+    #[test]
+    fn test_color_whole_lines() {
+        use crate::assert_diff_eq;
+
+        // --color highlights changed lines as a whole; there is no word-level
+        // highlighting of the part of a line that changed.
+        let params = Params {
+            from: "foo".into(),
+            to: "bar".into(),
+            color: true,
+            ..Default::default()
+        };
+        let diff = diff(b"one two three\n", b"one 2 three\n", &params);
+        let expected = [
+            "\x1b[1m--- foo\tTIMESTAMP\x1b[0m",
+            "\x1b[1m+++ bar\tTIMESTAMP\x1b[0m",
+            "\x1b[36m@@ -1 +1 @@\x1b[0m",
+            "\x1b[31m-one two three\x1b[0m",
+            "\x1b[32m+one 2 three\x1b[0m",
+            "",
+        ]
+        .join("\n");
+        assert_diff_eq!(diff, expected);
+    }
+
+    #[test]
+    fn test_unified_hunks_borrow_input() {
+        let from: Vec<u8> = (0..100_000)
+            .flat_map(|i| format!("line {i}\n").into_bytes())
+            .collect();
+        let mut to = from.clone();
+        to[0] = b'L';
+        let contains = |buffer: &[u8], line: &[u8]| {
+            buffer.as_ptr_range().contains(&line.as_ptr())
+                && line.as_ptr_range().end <= buffer.as_ptr_range().end
+        };
+        // With enough context, every line of the inputs ends up in the hunk
+        let params = Params {
+            context_count: 100_000,
+            ..Default::default()
+        };
+        let hunks = unified_hunks(&from, &to, &params);
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].lines.len(), 100_001);
+        for line in &hunks[0].lines {
+            match *line {
+                DiffLine::Context(line) => assert!(contains(&from, line)),
+                DiffLine::Expected(line) => assert!(contains(&from, line)),
+                DiffLine::Actual(line) => assert!(contains(&to, line)),
+                DiffLine::MissingNL => {}
+            }
+        }
+    }
 }
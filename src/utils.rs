@@ -3,11 +3,34 @@
 // For the full copyright and license information, please view the LICENSE-*
 // files that was distributed with this source code.
 
-use std::{ffi::OsString, io::Write};
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::ops::Deref;
+use std::path::Path;
+use std::time::SystemTime;
 
 use regex::Regex;
 use unicode_width::UnicodeWidthStr;
 
+use crate::params::Params;
+
+pub mod sha1;
+pub mod visible;
+
+/// Exit status legend shared by the `--help` output of every subcommand.
+pub const EXIT_STATUS_HELP: &str =
+    "Exit status is 0 if inputs are the same, 1 if different, 2 if trouble.";
+
+/// Print the `--version` output of the subcommand `name`, in the same form
+/// for every subcommand.
+pub fn print_version(name: &str) {
+    println!("{name} (uutils diffutils) {}", env!("CARGO_PKG_VERSION"));
+    println!("License MIT OR Apache-2.0.");
+    println!("A reimplementation of GNU diffutils; output aims to match GNU's.");
+}
+
 /// Replace tabs by spaces in the input line.
 /// Correctly handle multi-bytes characters.
 /// This assumes that line does not contain any line breaks (if it does, the result is undefined).
@@ -40,8 +63,8 @@ pub fn do_expand_tabs(line: &[u8], tabsize: usize) -> Vec<u8> {
 /// Write a single line to an output stream, expanding tabs to space if necessary.
 /// This assumes that line does not contain any line breaks
 /// (if it does and tabs are to be expanded to spaces, the result is undefined).
-pub fn do_write_line(
-    output: &mut Vec<u8>,
+pub fn do_write_line<W: Write>(
+    output: &mut W,
     line: &[u8],
     expand_tabs: bool,
     tabsize: usize,
@@ -53,23 +76,200 @@ pub fn do_write_line(
     }
 }
 
+/// Find the text shown after a hunk header with `-p`/`--show-c-function`:
+/// the last of the first `count` lines that starts like a C function does,
+/// with a letter, an underscore or a dollar sign. As in GNU diff, it is
+/// truncated to 40 bytes and trailing whitespace is dropped.
+#[must_use]
+pub fn find_function_context<'a>(lines: &[&'a [u8]], count: usize) -> Option<&'a [u8]> {
+    let line = lines[..count.min(lines.len())]
+        .iter()
+        .rev()
+        .find(|line| matches!(line.first(), Some(c) if c.is_ascii_alphabetic() || *c == b'_' || *c == b'$'))?;
+    let line = &line[..line.len().min(40)];
+    let end = line
+        .iter()
+        .rposition(|c| !c.is_ascii_whitespace())
+        .map_or(0, |i| i + 1);
+    Some(&line[..end])
+}
+
+/// Whether `contents` look like binary data. Like GNU diff, this looks for a
+/// NUL byte in the first block of the input only.
+#[must_use]
+pub fn is_binary(contents: &[u8]) -> bool {
+    const BINARY_CHECK_SIZE: usize = 4096;
+    contents[..contents.len().min(BINARY_CHECK_SIZE)].contains(&0)
+}
+
+/// Write the marker and separator that start the output of `line`. Under
+/// `--suppress-blank-empty` an empty line gets no trailing white space: the
+/// separator is left out and so is a blank marker.
+pub fn write_line_marker<W: Write>(
+    output: &mut W,
+    marker: &str,
+    separator: &str,
+    line: &[u8],
+    params: &Params,
+) -> io::Result<()> {
+    if line.is_empty() && params.suppress_blank_empty {
+        output.write_all(marker.trim_end().as_bytes())
+    } else {
+        output.write_all(marker.as_bytes())?;
+        output.write_all(separator.as_bytes())
+    }
+}
+
+/// Under `--color`, start a span of the SGR `code`, one of those of
+/// [`Palette`](crate::params::Palette).
+pub fn set_color<W: Write>(output: &mut W, enabled: bool, code: &str) -> io::Result<()> {
+    if enabled {
+        write!(output, "\x1b[{code}m")?;
+    }
+    Ok(())
+}
+
 /// Retrieves the modification time of the input file specified by file path
 /// If an error occurs, it returns the current system time
 pub fn get_modification_time(file_path: &str) -> String {
-    use chrono::{DateTime, Local};
     use std::fs;
-    use std::time::SystemTime;
 
     let modification_time: SystemTime = fs::metadata(file_path)
         .and_then(|m| m.modified())
         .unwrap_or(SystemTime::now());
 
+    format_modification_time(modification_time)
+}
+
+/// Like [`get_modification_time`] for the from-file, except that a missing
+/// file treated as empty by `--unidirectional-new-file` gets the Unix epoch,
+/// which tells `patch` to create it.
+pub fn get_from_modification_time(params: &Params) -> String {
+    if is_new_from_file(params) {
+        return format_modification_time(SystemTime::UNIX_EPOCH);
+    }
+    get_modification_time(&params.from.to_string_lossy())
+}
+
+/// Whether the from-file is missing and read as empty because of
+/// `--unidirectional-new-file`.
+#[must_use]
+pub fn is_new_from_file(params: &Params) -> bool {
+    params.unidirectional_new_file && !Path::new(&params.from).exists()
+}
+
+/// Remove the first `count` components from `path`, each with the run of
+/// separators that ends it, as `patch -p<count>` does. The file name itself
+/// is never removed.
+#[must_use]
+pub fn strip_path(path: &OsStr, count: usize) -> &OsStr {
+    let bytes = path.as_encoded_bytes();
+    let is_separator = |byte: &u8| std::path::is_separator(char::from(*byte));
+    let mut start = 0;
+    for _ in 0..count {
+        let Some(separator) = bytes[start..].iter().position(is_separator) else {
+            break;
+        };
+        let rest = &bytes[start + separator..];
+        let next = rest.iter().position(|byte| !is_separator(byte));
+        match next {
+            Some(next) => start += separator + next,
+            None => break,
+        }
+    }
+    split_os_str(path, start).1
+}
+
+/// Split `s` at byte `index` of its encoded form, which must be at either
+/// end or right after an ASCII byte, such as the `=` of an option or a `/`.
+///
+/// # Panics
+/// Panics if `index` is inside `s` and the byte before it is not ASCII.
+#[must_use]
+pub fn split_os_str(s: &OsStr, index: usize) -> (&OsStr, &OsStr) {
+    let (head, tail) = s.as_encoded_bytes().split_at(index);
+    assert!(tail.is_empty() || head.last().is_none_or(u8::is_ascii));
+    // SAFETY: an ASCII byte is a whole character in every encoding OsStr
+    // uses, so the split falls between characters, as it does at either end.
+    unsafe {
+        (
+            OsStr::from_encoded_bytes_unchecked(head),
+            OsStr::from_encoded_bytes_unchecked(tail),
+        )
+    }
+}
+
+/// `path` relative to `dir` if it lies strictly inside it, otherwise `path`
+/// unchanged. Only whole components are compared, so no file system access
+/// is needed and `a/bc` is not inside `a/b`.
+#[must_use]
+pub fn relative_path<'a>(path: &'a OsStr, dir: &OsStr) -> &'a OsStr {
+    match Path::new(path).strip_prefix(dir) {
+        Ok(rest) if !rest.as_os_str().is_empty() => rest.as_os_str(),
+        _ => path,
+    }
+}
+
+/// The name shown for a file in headers, with `--relative-to` and then
+/// `--strip-paths` applied.
+#[must_use]
+pub fn header_file_name(path: &OsStr, params: &Params) -> Vec<u8> {
+    let path = match &params.relative_to {
+        Some(dir) => relative_path(path, dir),
+        None => path,
+    };
+    strip_path(path, params.strip_paths)
+        .as_encoded_bytes()
+        .to_vec()
+}
+
+/// The from-file half of a context or unified header: its `--label` if one
+/// was given, byte for byte, otherwise its name and modification time.
+/// A label is shown as given, even with `--strip-paths`.
+#[must_use]
+pub fn from_file_header(params: &Params) -> Vec<u8> {
+    match &params.from_label {
+        Some(label) => label.as_encoded_bytes().to_vec(),
+        None => [
+            header_file_name(&params.from, params),
+            format!("\t{}", get_from_modification_time(params)).into_bytes(),
+        ]
+        .concat(),
+    }
+}
+
+/// The to-file half of a context or unified header, as [`from_file_header`].
+#[must_use]
+pub fn to_file_header(params: &Params) -> Vec<u8> {
+    match &params.to_label {
+        Some(label) => label.as_encoded_bytes().to_vec(),
+        None => [
+            header_file_name(&params.to, params),
+            format!("\t{}", get_modification_time(&params.to.to_string_lossy())).into_bytes(),
+        ]
+        .concat(),
+    }
+}
+
+fn format_modification_time(modification_time: SystemTime) -> String {
+    use chrono::{DateTime, Local};
+
     let modification_time: DateTime<Local> = modification_time.into();
-    let modification_time: String = modification_time
+    modification_time
         .format("%Y-%m-%d %H:%M:%S%.9f %z")
-        .to_string();
+        .to_string()
+}
 
-    modification_time
+/// `error` as GNU tools show it, without the ` (os error N)` that the
+/// `Display` of [`std::io::Error`] appends.
+#[must_use]
+pub fn format_io_error(error: &std::io::Error) -> String {
+    // std::io::Error's display trait outputs "{detail} (os error {code})"
+    // but we want only the {detail} (error string) part
+    let error_code_re = Regex::new(r"\ \(os\ error\ \d+\)$").unwrap();
+    error_code_re
+        .replace(error.to_string().as_str(), "")
+        .into_owned()
 }
 
 pub fn format_failure_to_read_input_file(
@@ -77,14 +277,11 @@ pub fn format_failure_to_read_input_file(
     filepath: &OsString,
     error: &std::io::Error,
 ) -> String {
-    // std::io::Error's display trait outputs "{detail} (os error {code})"
-    // but we want only the {detail} (error string) part
-    let error_code_re = Regex::new(r"\ \(os\ error\ \d+\)$").unwrap();
     format!(
         "{}: {}: {}",
         executable.to_string_lossy(),
         filepath.to_string_lossy(),
-        error_code_re.replace(error.to_string().as_str(), ""),
+        format_io_error(error),
     )
 }
 
@@ -99,10 +296,383 @@ pub fn report_failure_to_read_input_file(
     );
 }
 
+/// The contents of an input file: either read into memory or, with the
+/// `mmap` feature, mapped from a regular file.
+pub enum FileContents {
+    Read(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl Deref for FileContents {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileContents::Read(contents) => contents,
+            #[cfg(feature = "mmap")]
+            FileContents::Mapped(map) => map,
+        }
+    }
+}
+
+/// Read the whole of an input file, or of stdin if `filepath` is `-`.
+/// With the `mmap` feature, regular files are mapped instead of read.
+pub fn read_file_contents(filepath: &OsString) -> io::Result<FileContents> {
+    let mut contents = Vec::new();
+    if filepath == "-" {
+        io::stdin().read_to_end(&mut contents)?;
+    } else {
+        let mut file = File::open(filepath)?;
+        #[cfg(feature = "mmap")]
+        if let Some(map) = map_file(&file) {
+            return Ok(FileContents::Mapped(map));
+        }
+        file.read_to_end(&mut contents)?;
+    }
+    Ok(FileContents::Read(contents))
+}
+
+/// Map a regular file into memory. Returns `None` for pipes, devices and
+/// other special files, or if mapping fails, so that the caller can fall
+/// back on reading the file.
+///
+/// As with GNU diffutils, files that are modified while they are being
+/// compared are not supported: the result is then unspecified, and the
+/// process may be killed by `SIGBUS` if a mapped file is truncated.
+#[cfg(feature = "mmap")]
+pub fn map_file(file: &File) -> Option<memmap2::Mmap> {
+    if !file.metadata().ok()?.is_file() {
+        return None;
+    }
+    // SAFETY: the map is only ever read from, and concurrent modification
+    // of the file is documented above as unsupported.
+    unsafe { memmap2::Mmap::map(file) }.ok()
+}
+
+/// The part of an input that is compared, prepared as the `diff` command
+/// does: a byte order mark is dropped with `--ignore-bom`, then the first
+/// `--skip` bytes, and then `--strip-trailing-cr` applies to the rest.
+#[must_use]
+pub fn prepare_input<'a>(contents: &'a [u8], params: &Params) -> Cow<'a, [u8]> {
+    let contents = if params.ignore_bom {
+        strip_bom(contents)
+    } else {
+        contents
+    };
+    let contents = &contents[params.skip.min(contents.len())..];
+    if params.strip_trailing_cr {
+        Cow::Owned(strip_trailing_cr(contents))
+    } else {
+        Cow::Borrowed(contents)
+    }
+}
+
+/// `contents` without a leading UTF-8 or UTF-16 byte order mark, for
+/// `--ignore-bom`. Only one mark is removed, and nothing else is decoded.
+#[must_use]
+pub fn strip_bom(contents: &[u8]) -> &[u8] {
+    for bom in [&b"\xEF\xBB\xBF"[..], b"\xFF\xFE", b"\xFE\xFF"] {
+        if let Some(rest) = contents.strip_prefix(bom) {
+            return rest;
+        }
+    }
+    contents
+}
+
+/// Remove the carriage return from every line ending in `\r\n`. As in GNU
+/// diff, a `\r` that is not followed by a newline, such as at the end of an
+/// unterminated last line, is kept.
+#[must_use]
+pub fn strip_trailing_cr(contents: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(contents.len());
+    for line in contents.split_inclusive(|&c| c == b'\n') {
+        match line.strip_suffix(b"\r\n") {
+            Some(line) => {
+                result.extend_from_slice(line);
+                result.push(b'\n');
+            }
+            None => result.extend_from_slice(line),
+        }
+    }
+    result
+}
+
+/// What is compared of `line`, without its newline, under the options in
+/// `params` that make diff ignore some differences between lines: tabs are
+/// expanded to spaces under `-E`, then trailing white space is removed under
+/// `-Z`. As in GNU diff, white space is what C's `isspace` accepts, carriage
+/// returns included.
+#[must_use]
+pub fn comparison_key<'a>(line: &'a [u8], params: &Params) -> Cow<'a, [u8]> {
+    let mut key = Cow::Borrowed(line);
+    if params.ignore_tab_expansion && line.contains(&b'\t') {
+        key = Cow::Owned(do_expand_tabs(line, params.tabsize));
+    }
+    if params.ignore_trailing_space {
+        let end = key
+            .iter()
+            .rposition(|&c| !matches!(c, b' ' | b'\t' | b'\r' | b'\x0b' | b'\x0c'))
+            .map_or(0, |i| i + 1);
+        match &mut key {
+            Cow::Borrowed(key) => *key = &key[..end],
+            Cow::Owned(key) => key.truncate(end),
+        }
+    }
+    key
+}
+
+// A line compared by its key and written out as it was read. Some formats
+// split lines with their newline, which is then compared separately so that
+// a last line lacking one still differs.
+struct KeyedLine<'a> {
+    line: &'a [u8],
+    key: Cow<'a, [u8]>,
+    newline: bool,
+}
+
+impl PartialEq for KeyedLine<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && self.newline == other.newline
+    }
+}
+
+// Diff two slices, setting aside their common prefix and suffix except for
+// the last and first `horizon` lines of them, which stay in the slices
+// compared so that changes can be matched up across them. diff::slice sets
+// aside the whole prefix and suffix itself, so that is all it is used for.
+fn diff_slices<'a, T: PartialEq>(
+    left: &'a [T],
+    right: &'a [T],
+    horizon: usize,
+) -> Vec<diff::Result<&'a T>> {
+    if horizon == 0 {
+        return diff::slice(left, right);
+    }
+    let leading = left.iter().zip(right).take_while(|(l, r)| l == r).count();
+    let trailing = left[leading..]
+        .iter()
+        .rev()
+        .zip(right[leading..].iter().rev())
+        .take_while(|(l, r)| l == r)
+        .count();
+    let prefix = leading.saturating_sub(horizon);
+    let suffix = trailing.saturating_sub(horizon);
+    let both = |(l, r)| diff::Result::Both(l, r);
+    let middle = (
+        &left[prefix..left.len() - suffix],
+        &right[prefix..right.len() - suffix],
+    );
+
+    // Lengths of the longest common subsequences of the heads of the middle
+    // slices, walked back from the end in the same way as diff::slice.
+    let (n, m) = (middle.0.len(), middle.1.len());
+    let mut table = vec![0u32; (n + 1) * (m + 1)];
+    let at = |i: usize, j: usize| i * (m + 1) + j;
+    for i in 0..n {
+        for j in 0..m {
+            table[at(i + 1, j + 1)] = if middle.0[i] == middle.1[j] {
+                table[at(i, j)] + 1
+            } else {
+                table[at(i, j + 1)].max(table[at(i + 1, j)])
+            };
+        }
+    }
+    let mut changed = Vec::with_capacity(n.max(m));
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        if j > 0 && (i == 0 || table[at(i, j)] == table[at(i, j - 1)]) {
+            j -= 1;
+            changed.push(diff::Result::Right(&middle.1[j]));
+        } else if i > 0 && (j == 0 || table[at(i, j)] == table[at(i - 1, j)]) {
+            i -= 1;
+            changed.push(diff::Result::Left(&middle.0[i]));
+        } else {
+            i -= 1;
+            j -= 1;
+            changed.push(diff::Result::Both(&middle.0[i], &middle.1[j]));
+        }
+    }
+
+    let mut result = Vec::with_capacity(left.len().max(right.len()));
+    result.extend(left[..prefix].iter().zip(&right[..prefix]).map(both));
+    result.extend(changed.into_iter().rev());
+    result.extend(
+        left[left.len() - suffix..]
+            .iter()
+            .zip(&right[right.len() - suffix..])
+            .map(both),
+    );
+    result
+}
+
+/// Diff two sequences of lines, comparing them by their [`comparison_key`].
+/// The results hold the lines themselves, so that the output shows them as
+/// they are in the inputs; lines paired as unchanged come first from
+/// `expected`, then from `actual`. Of the lines common to the start and end
+/// of both, only the `horizon_lines` closest to the changes are compared.
+#[must_use]
+pub fn diff_lines<'a>(
+    expected: &[&'a [u8]],
+    actual: &[&'a [u8]],
+    params: &Params,
+) -> Vec<diff::Result<&'a [u8]>> {
+    let map = |result: diff::Result<&&'a [u8]>| match result {
+        diff::Result::Left(left) => diff::Result::Left(*left),
+        diff::Result::Both(left, right) => diff::Result::Both(*left, *right),
+        diff::Result::Right(right) => diff::Result::Right(*right),
+    };
+    if !params.ignore_trailing_space && !params.ignore_tab_expansion {
+        return diff_slices(expected, actual, params.horizon_lines)
+            .into_iter()
+            .map(map)
+            .collect();
+    }
+    let keyed = |lines: &[&'a [u8]]| -> Vec<KeyedLine<'a>> {
+        lines
+            .iter()
+            .map(|line| {
+                let (body, newline) = match line.strip_suffix(b"\n") {
+                    Some(body) => (body, true),
+                    None => (*line, false),
+                };
+                KeyedLine {
+                    line,
+                    key: comparison_key(body, params),
+                    // -Z counts the newline as trailing white space
+                    newline: newline && !params.ignore_trailing_space,
+                }
+            })
+            .collect()
+    };
+    let (expected, actual) = (keyed(expected), keyed(actual));
+    diff_slices(&expected, &actual, params.horizon_lines)
+        .into_iter()
+        .map(|result| match result {
+            diff::Result::Left(left) => diff::Result::Left(left.line),
+            diff::Result::Both(left, right) => diff::Result::Both(left.line, right.line),
+            diff::Result::Right(right) => diff::Result::Right(right.line),
+        })
+        .collect()
+}
+
+/// Parse a byte count with an optional size suffix, as taken by cmp's
+/// `--ignore-initial` and `--bytes` and diff's `--skip`: `kB`, `MB`, ... are
+/// powers of 1000 and `K`, `M`, ... powers of 1024. Counts too large for a
+/// `usize` saturate.
+pub(crate) fn parse_byte_count(s: &str) -> Result<usize, String> {
+    let invalid = || format!("invalid byte count '{s}'");
+    let suffix_start = s.find(|b: char| !b.is_ascii_digit()).unwrap_or(s.len());
+    let num = match s[..suffix_start].parse::<usize>() {
+        Ok(num) => num,
+        Err(e) if *e.kind() == std::num::IntErrorKind::PosOverflow => usize::MAX,
+        Err(_) => return Err(invalid()),
+    };
+    if suffix_start == s.len() {
+        return Ok(num);
+    }
+
+    // Note that GNU cmp advertises supporting up to Y, but fails if you try
+    // to actually use anything beyond E. Here Z and Y saturate, as do T, P
+    // and E where a usize is only 32 bits wide.
+    let multiplier: u128 = match &s[suffix_start..] {
+        "kB" => 1_000,
+        "K" => 1_024,
+        "MB" => 1_000_000,
+        "M" => 1_048_576,
+        "GB" => 1_000_000_000,
+        "G" => 1_073_741_824,
+        "TB" => 1_000_000_000_000,
+        "T" => 1_099_511_627_776,
+        "PB" => 1_000_000_000_000_000,
+        "P" => 1_125_899_906_842_624,
+        "EB" => 1_000_000_000_000_000_000,
+        "E" => 1_152_921_504_606_846_976,
+        "ZB" => 1_000_000_000_000_000_000_000,
+        "Z" => 1_180_591_620_717_411_303_424,
+        "YB" => 1_000_000_000_000_000_000_000_000,
+        "Y" => 1_208_925_819_614_629_174_706_176,
+        _ => return Err(invalid()),
+    };
+    Ok(usize::try_from((num as u128).saturating_mul(multiplier)).unwrap_or(usize::MAX))
+}
+
+/// Whether `POSIXLY_CORRECT` is set, in which case option parsing must stop
+/// at the first operand, as GNU getopt does.
+pub fn is_posixly_correct() -> bool {
+    std::env::var_os("POSIXLY_CORRECT").is_some()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    mod parse_byte_count {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn plain() {
+            assert_eq!(parse_byte_count("0"), Ok(0));
+            assert_eq!(parse_byte_count("1234"), Ok(1234));
+            assert_eq!(
+                parse_byte_count("99999999999999999999999999999"),
+                Ok(usize::MAX)
+            );
+        }
+
+        #[test]
+        fn suffixes() {
+            for (suffix, multiplier) in [
+                ("kB", 1_000),
+                ("K", 1 << 10),
+                ("MB", 1_000_000),
+                ("M", 1 << 20),
+                ("GB", 1_000_000_000),
+                ("G", 1 << 30),
+            ] {
+                assert_eq!(parse_byte_count(&format!("2{suffix}")), Ok(2 * multiplier));
+                assert_eq!(parse_byte_count(&format!("0{suffix}")), Ok(0));
+            }
+        }
+
+        #[test]
+        #[cfg(target_pointer_width = "64")]
+        fn large_suffixes() {
+            for (suffix, multiplier) in [
+                ("TB", 1_000_000_000_000),
+                ("T", 1 << 40),
+                ("PB", 1_000_000_000_000_000),
+                ("P", 1 << 50),
+                ("EB", 1_000_000_000_000_000_000),
+                ("E", 1 << 60),
+            ] {
+                assert_eq!(parse_byte_count(&format!("3{suffix}")), Ok(3 * multiplier));
+            }
+            // Only E and beyond can overflow a 64-bit count
+            assert_eq!(parse_byte_count("16E"), Ok(usize::MAX));
+        }
+
+        #[test]
+        fn saturating_suffixes() {
+            for suffix in ["ZB", "Z", "YB", "Y"] {
+                assert_eq!(parse_byte_count(&format!("1{suffix}")), Ok(usize::MAX));
+                assert_eq!(parse_byte_count(&format!("0{suffix}")), Ok(0));
+            }
+        }
+
+        #[test]
+        fn invalid() {
+            for s in ["", "K", "-1", "1k", "1Q", "1KB", "1 K", "x1"] {
+                assert_eq!(
+                    parse_byte_count(s),
+                    Err(format!("invalid byte count '{s}'")),
+                    "{s:?}"
+                );
+            }
+        }
+    }
+
     mod expand_tabs {
         use super::*;
         use pretty_assertions::assert_eq;
@@ -122,6 +692,16 @@ mod tests {
             assert_tab_expansion("foo\tbarr\tbaz", 2, "foo barr  baz");
         }
 
+        #[test]
+        fn leading_tab() {
+            assert_tab_expansion("\tx", 4, "    x");
+            assert_tab_expansion("\tx", 8, "        x");
+            assert_tab_expansion("ab\tx", 4, "ab  x");
+            assert_tab_expansion("abcd\tx", 4, "abcd    x");
+            assert_tab_expansion("é\tx", 4, "é   x");
+            assert_tab_expansion("😉\tx", 8, "😉      x");
+        }
+
         #[test]
         fn multibyte_chars() {
             assert_tab_expansion("foo\tépée\tbaz", 8, "foo     épée    baz");
@@ -164,6 +744,40 @@ mod tests {
         }
     }
 
+    mod function_context {
+        use super::*;
+
+        #[test]
+        fn basics() {
+            let lines: Vec<&[u8]> = vec![
+                b"#include <stdio.h>",
+                b"",
+                b"int main(void) {  ",
+                b"    return 0;",
+                b"}",
+                b"_start",
+            ];
+            assert_eq!(find_function_context(&lines, 0), None);
+            assert_eq!(find_function_context(&lines, 2), None);
+            assert_eq!(
+                find_function_context(&lines, 3),
+                Some(&b"int main(void) {"[..])
+            );
+            assert_eq!(
+                find_function_context(&lines, 5),
+                Some(&b"int main(void) {"[..])
+            );
+            assert_eq!(find_function_context(&lines, 6), Some(&b"_start"[..]));
+            assert_eq!(find_function_context(&lines, 60), Some(&b"_start"[..]));
+        }
+
+        #[test]
+        fn truncated() {
+            let long = b"static void a_function_with_a_very_long_name(int argument)";
+            assert_eq!(find_function_context(&[&long[..]], 1), Some(&long[..40]));
+        }
+    }
+
     mod modification_time {
         use super::*;
 
@@ -204,5 +818,320 @@ mod tests {
 
             assert!(m_time > current_time);
         }
+
+        #[test]
+        fn unidirectional_new_file() {
+            use chrono::{DateTime, Local};
+
+            let mut params = Params {
+                from: "target/utils/invalid-file".into(),
+                unidirectional_new_file: true,
+                ..Default::default()
+            };
+            let epoch: DateTime<Local> = SystemTime::UNIX_EPOCH.into();
+            let epoch = epoch.format("%Y-%m-%d %H:%M:%S%.9f %z").to_string();
+            assert_eq!(epoch, get_from_modification_time(&params));
+
+            // An existing file keeps its own modification time
+            params.from = "Cargo.toml".into();
+            assert_eq!(
+                get_modification_time("Cargo.toml"),
+                get_from_modification_time(&params)
+            );
+        }
+    }
+
+    mod file_contents {
+        use super::*;
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        #[test]
+        fn regular_file() {
+            let mut temp = NamedTempFile::new().unwrap();
+            temp.write_all(b"foo\nbar\n").unwrap();
+            let contents = read_file_contents(&temp.path().into()).unwrap();
+            assert_eq!(&*contents, b"foo\nbar\n");
+            #[cfg(feature = "mmap")]
+            assert!(matches!(contents, FileContents::Mapped(_)));
+        }
+
+        #[test]
+        fn empty_file() {
+            let temp = NamedTempFile::new().unwrap();
+            let contents = read_file_contents(&temp.path().into()).unwrap();
+            assert!(contents.is_empty());
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn special_file() {
+            let contents = read_file_contents(&OsString::from("/dev/null")).unwrap();
+            assert!(matches!(contents, FileContents::Read(_)));
+            assert!(contents.is_empty());
+        }
+    }
+
+    mod is_binary {
+        use super::*;
+
+        #[test]
+        fn basics() {
+            assert!(!is_binary(b""));
+            assert!(!is_binary(b"foo\nbar\n"));
+            assert!(is_binary(b"foo\0bar\n"));
+            assert!(is_binary(b"\0"));
+        }
+
+        #[test]
+        fn only_first_block() {
+            let mut contents = vec![b'x'; 4095];
+            contents.push(0);
+            assert!(is_binary(&contents));
+            contents.insert(0, b'x');
+            assert!(!is_binary(&contents));
+        }
+    }
+
+    mod comparison_key {
+        use super::*;
+
+        #[test]
+        fn ignore_trailing_space() {
+            let params = Params {
+                ignore_trailing_space: true,
+                ..Default::default()
+            };
+            assert_eq!(*comparison_key(b"a \t\r", &params), *b"a");
+            assert_eq!(*comparison_key(b" a b ", &params), *b" a b");
+            assert_eq!(*comparison_key(b" \t", &params), *b"");
+            assert_eq!(*comparison_key(b"a ", &Params::default()), *b"a ");
+        }
+
+        #[test]
+        fn ignore_tab_expansion() {
+            let params = Params {
+                ignore_tab_expansion: true,
+                ..Default::default()
+            };
+            assert_eq!(*comparison_key(b"\tx", &params), *b"        x");
+            assert_eq!(*comparison_key(b"ab\tx", &params), *b"ab      x");
+            assert_eq!(*comparison_key(b"x y", &params), *b"x y");
+            assert!(matches!(comparison_key(b"x y", &params), Cow::Borrowed(_)));
+            let params = Params {
+                tabsize: 4,
+                ..params
+            };
+            assert_eq!(*comparison_key(b"\tx", &params), *b"    x");
+            // Tabs are expanded before trailing white space is removed
+            let params = Params {
+                ignore_trailing_space: true,
+                ..params
+            };
+            assert_eq!(*comparison_key(b"\tx\t", &params), *b"    x");
+        }
+
+        #[test]
+        fn diff_lines_keeps_lines() {
+            let params = Params {
+                ignore_trailing_space: true,
+                ..Default::default()
+            };
+            let expected: [&[u8]; 2] = [b"a \n", b"b\n"];
+            let actual: [&[u8]; 2] = [b"a\n", b"c"];
+            assert_eq!(
+                diff_lines(&expected, &actual, &params),
+                [
+                    diff::Result::Both(&b"a \n"[..], &b"a\n"[..]),
+                    diff::Result::Left(&b"b\n"[..]),
+                    diff::Result::Right(&b"c"[..]),
+                ]
+            );
+            // Without -Z, a missing newline still makes lines differ
+            assert_eq!(
+                diff_lines(&[b"a\n"], &[b"a"], &Params::default()),
+                [
+                    diff::Result::Left(&b"a\n"[..]),
+                    diff::Result::Right(&b"a"[..])
+                ]
+            );
+        }
+
+        #[test]
+        fn diff_lines_horizon() {
+            let expected: [&[u8]; 4] = [b"p\n", b"x\n", b"a\n", b"s\n"];
+            let actual: [&[u8]; 6] = [b"p\n", b"y\n", b"a\n", b"b\n", b"a\n", b"s\n"];
+            let diff = |horizon_lines| {
+                let params = Params {
+                    horizon_lines,
+                    ..Default::default()
+                };
+                diff_lines(&expected, &actual, &params)
+            };
+            let same = |line: &'static [u8]| diff::Result::Both(line, line);
+            // With the common "a" set aside, x is changed into y, a and b
+            assert_eq!(
+                diff(0),
+                [
+                    same(b"p\n"),
+                    diff::Result::Left(&b"x\n"[..]),
+                    diff::Result::Right(&b"y\n"[..]),
+                    diff::Result::Right(&b"a\n"[..]),
+                    diff::Result::Right(&b"b\n"[..]),
+                    same(b"a\n"),
+                    same(b"s\n"),
+                ]
+            );
+            // Kept in view, it pairs with the first "a" instead
+            let kept = [
+                same(b"p\n"),
+                diff::Result::Left(&b"x\n"[..]),
+                diff::Result::Right(&b"y\n"[..]),
+                same(b"a\n"),
+                diff::Result::Right(&b"b\n"[..]),
+                diff::Result::Right(&b"a\n"[..]),
+                same(b"s\n"),
+            ];
+            assert_eq!(diff(1), kept);
+            assert_eq!(diff(3), kept);
+        }
+    }
+
+    mod split_os_str {
+        use super::*;
+
+        #[test]
+        fn basics() {
+            let s = OsStr::new("--label=x/é");
+            assert_eq!(split_os_str(s, 0), (OsStr::new(""), s));
+            assert_eq!(
+                split_os_str(s, 7),
+                (OsStr::new("--label"), OsStr::new("=x/é"))
+            );
+            assert_eq!(
+                split_os_str(s, 10),
+                (OsStr::new("--label=x/"), OsStr::new("é"))
+            );
+            assert_eq!(split_os_str(s, 12), (s, OsStr::new("")));
+        }
+
+        #[test]
+        #[should_panic]
+        fn inside_character() {
+            let _ = split_os_str(OsStr::new("é"), 1);
+        }
+    }
+
+    mod strip_path {
+        use super::*;
+
+        fn strip(path: &str, count: usize) -> &str {
+            strip_path(OsStr::new(path), count).to_str().unwrap()
+        }
+
+        #[test]
+        fn basics() {
+            assert_eq!(strip("a/b/c", 0), "a/b/c");
+            assert_eq!(strip("a/b/c", 1), "b/c");
+            assert_eq!(strip("a/b/c", 2), "c");
+            assert_eq!(strip("/tmp/a", 1), "tmp/a");
+            assert_eq!(strip("./a//b", 1), "a//b");
+            assert_eq!(strip("a//b", 1), "b");
+        }
+
+        #[test]
+        fn keeps_file_name() {
+            assert_eq!(strip("a/b/c", 5), "c");
+            assert_eq!(strip("c", 1), "c");
+            assert_eq!(strip("a/", 1), "a/");
+        }
+    }
+
+    mod relative_path {
+        use super::*;
+
+        fn relative<'a>(path: &'a str, dir: &str) -> &'a str {
+            relative_path(OsStr::new(path), OsStr::new(dir))
+                .to_str()
+                .unwrap()
+        }
+
+        #[test]
+        fn basics() {
+            assert_eq!(relative("/tmp/x/a/b", "/tmp/x"), "a/b");
+            assert_eq!(relative("/tmp/x/a/b", "/tmp/x/"), "a/b");
+            assert_eq!(relative("a/b", "a"), "b");
+        }
+
+        #[test]
+        fn outside_dir() {
+            assert_eq!(relative("/tmp/xy/a", "/tmp/x"), "/tmp/xy/a");
+            assert_eq!(relative("a/b", "/tmp"), "a/b");
+            assert_eq!(relative("/tmp/x", "/tmp/x"), "/tmp/x");
+        }
+    }
+
+    mod prepare_input {
+        use super::*;
+
+        #[test]
+        fn unchanged_by_default() {
+            let params = Params::default();
+            let input = b"\xEF\xBB\xBFa\r\nb\r\n";
+            assert!(matches!(prepare_input(input, &params), Cow::Borrowed(i) if i == input));
+        }
+
+        #[test]
+        fn order() {
+            // The mark goes first, so --skip counts from after it
+            let params = Params {
+                ignore_bom: true,
+                skip: 1,
+                strip_trailing_cr: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                &*prepare_input(b"\xEF\xBB\xBFxa\r\nb\r\n", &params),
+                b"a\nb\n"
+            );
+            assert_eq!(&*prepare_input(b"", &params), b"");
+        }
+    }
+
+    mod strip_bom {
+        use super::*;
+
+        #[test]
+        fn basics() {
+            assert_eq!(strip_bom(b"\xEF\xBB\xBFfoo\n"), b"foo\n");
+            assert_eq!(strip_bom(b"\xFF\xFEf\0"), b"f\0");
+            assert_eq!(strip_bom(b"\xFE\xFF\0f"), b"\0f");
+            assert_eq!(strip_bom(b"\xEF\xBB\xBF\xEF\xBB\xBF"), b"\xEF\xBB\xBF");
+        }
+
+        #[test]
+        fn no_bom() {
+            assert_eq!(strip_bom(b""), b"");
+            assert_eq!(strip_bom(b"foo\n"), b"foo\n");
+            assert_eq!(strip_bom(b"\xEF\xBB"), b"\xEF\xBB");
+            assert_eq!(strip_bom(b"a\xEF\xBB\xBF"), b"a\xEF\xBB\xBF");
+        }
+    }
+
+    mod strip_trailing_cr {
+        use super::*;
+
+        #[test]
+        fn basics() {
+            assert_eq!(strip_trailing_cr(b""), b"");
+            assert_eq!(strip_trailing_cr(b"foo\r\nbar\n"), b"foo\nbar\n");
+            assert_eq!(strip_trailing_cr(b"foo\r\r\n"), b"foo\r\n");
+            assert_eq!(strip_trailing_cr(b"a\rb\r\n"), b"a\rb\n");
+        }
+
+        #[test]
+        fn unterminated_line() {
+            assert_eq!(strip_trailing_cr(b"foo\r\nbar\r"), b"foo\nbar\r");
+        }
     }
 }
@@ -0,0 +1,140 @@
+// This file is part of the uutils diffutils package.
+//
+// For the full copyright and license information, please view the LICENSE-*
+// files that was distributed with this source code.
+
+//! Rendering of bytes in the visible notation of `cat -v`, as used by
+//! `cmp -b` and `cmp -l -b`.
+//!
+//! Printable ASCII bytes stand for themselves, control characters are
+//! written `^X` (`^?` for DEL), and bytes with the high bit set are written
+//! `M-` followed by the notation of the byte without it.
+//!
+//! The notation is the same in every locale, as in GNU cmp. A byte with the
+//! high bit set is only a piece of a character in a UTF-8 locale, so it is
+//! never printed as is, whatever `LC_ALL`, `LC_CTYPE` or `LANG` say.
+
+use std::fmt;
+use std::io::{self, Write};
+
+/// The visible form of a byte, at most 4 ASCII characters long (`M-^?`).
+///
+/// The [`Display`](fmt::Display) implementation honors width and alignment,
+/// so `format!("{:4}", ByteDisplay::new(b))` pads to a fixed column.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ByteDisplay {
+    buf: [u8; 4],
+    len: u8,
+}
+
+impl ByteDisplay {
+    #[must_use]
+    pub fn new(byte: u8) -> Self {
+        let mut buf = [0; 4];
+        let mut len = 0;
+        let mut byte = byte;
+        if byte >= 128 {
+            buf[..2].copy_from_slice(b"M-");
+            len = 2;
+            byte -= 128;
+        }
+        if byte < 32 {
+            buf[len] = b'^';
+            len += 1;
+            byte += 64;
+        } else if byte == 127 {
+            buf[len] = b'^';
+            len += 1;
+            byte = b'?';
+        }
+        buf[len] = byte;
+        len += 1;
+        Self {
+            buf,
+            len: len as u8,
+        }
+    }
+
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len as usize]
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        // SAFETY: every byte written by `new` is ASCII.
+        unsafe { std::str::from_utf8_unchecked(self.as_bytes()) }
+    }
+
+    /// The number of terminal columns taken by the visible form, which is
+    /// also its length in bytes.
+    #[must_use]
+    pub fn width(&self) -> usize {
+        self.len as usize
+    }
+}
+
+impl fmt::Display for ByteDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// Write the visible form of `byte` to `output`, returning its width.
+pub fn write_visible<W: Write>(output: &mut W, byte: u8) -> io::Result<usize> {
+    let visible = ByteDisplay::new(byte);
+    output.write_all(visible.as_bytes())?;
+    Ok(visible.width())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    // The notation spelled out range by range, independently of `new`.
+    fn expected(byte: u8) -> String {
+        let (prefix, low) = if byte >= 128 {
+            ("M-", byte - 128)
+        } else {
+            ("", byte)
+        };
+        match low {
+            0..=31 => format!("{prefix}^{}", (low + 64) as char),
+            127 => format!("{prefix}^?"),
+            _ => format!("{prefix}{}", low as char),
+        }
+    }
+
+    #[test]
+    fn all_bytes() {
+        for byte in 0..=u8::MAX {
+            let visible = ByteDisplay::new(byte);
+            assert_eq!(visible.as_str(), expected(byte), "byte {byte}");
+            assert_eq!(visible.width(), visible.as_bytes().len());
+            assert!(visible.as_bytes().is_ascii());
+
+            let mut output = Vec::new();
+            assert_eq!(write_visible(&mut output, byte).unwrap(), visible.width());
+            assert_eq!(output, visible.as_bytes());
+        }
+    }
+
+    #[test]
+    fn examples() {
+        assert_eq!(ByteDisplay::new(b'a').as_str(), "a");
+        assert_eq!(ByteDisplay::new(b' ').as_str(), " ");
+        assert_eq!(ByteDisplay::new(b'\n').as_str(), "^J");
+        assert_eq!(ByteDisplay::new(0x7f).as_str(), "^?");
+        assert_eq!(ByteDisplay::new(0x80).as_str(), "M-^@");
+        assert_eq!(ByteDisplay::new(0xe9).as_str(), "M-i");
+        assert_eq!(ByteDisplay::new(0xff).as_str(), "M-^?");
+    }
+
+    #[test]
+    fn display_padding() {
+        assert_eq!(format!("{:4}|", ByteDisplay::new(b'\t')), "^I  |");
+        assert_eq!(format!("{:>4}|", ByteDisplay::new(b'x')), "   x|");
+        assert_eq!(format!("{:2}|", ByteDisplay::new(0xff)), "M-^?|");
+    }
+}
@@ -9,6 +9,7 @@ use std::fs::File;
 #[cfg(not(windows))]
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::path::Path;
 use tempfile::{tempdir, NamedTempFile};
 
 // Integration tests for the diffutils command
@@ -44,6 +45,23 @@ mod common {
         Ok(())
     }
 
+    #[test]
+    fn help_documents_exit_status() -> Result<(), Box<dyn std::error::Error>> {
+        for subcmd in ["diff", "cmp"] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.arg(subcmd);
+            cmd.arg("--help");
+            cmd.assert()
+                .code(predicate::eq(0))
+                .success()
+                .stdout(predicate::str::starts_with(format!("Usage: {subcmd} ")))
+                .stdout(predicate::str::contains(
+                    "Exit status is 0 if inputs are the same, 1 if different, 2 if trouble.\n",
+                ));
+        }
+        Ok(())
+    }
+
     #[test]
     fn cannot_read_files() -> Result<(), Box<dyn std::error::Error>> {
         let file = NamedTempFile::new()?;
@@ -116,6 +134,15 @@ mod diff {
                 .success()
                 .stdout(predicate::str::is_empty());
         }
+
+        // The JSON format still gives an array, with no hunks in it
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.args(["diff", "--format=json"]);
+        cmd.arg(file.path()).arg(file.path());
+        cmd.assert()
+            .code(predicate::eq(0))
+            .success()
+            .stdout(predicate::eq("[]\n"));
         Ok(())
     }
 
@@ -124,7 +151,7 @@ mod diff {
         // same file
         let mut file1 = NamedTempFile::new()?;
         file1.write_all("foo\n".as_bytes())?;
-        for option in ["", "-u", "-c", "-e"] {
+        for option in ["", "-u", "-c", "-e", "-n", "--format=json", "-q"] {
             let mut cmd = Command::cargo_bin("diffutils")?;
             cmd.arg("diff");
             if !option.is_empty() {
@@ -135,7 +162,12 @@ mod diff {
                 .code(predicate::eq(0))
                 .success()
                 .stdout(predicate::eq(format!(
-                    "Files {} and {} are identical\n",
+                    "{}Files {} and {} are identical\n",
+                    if option == "--format=json" {
+                        "[]\n"
+                    } else {
+                        ""
+                    },
                     file1.path().to_string_lossy(),
                     file1.path().to_string_lossy(),
                 )));
@@ -143,7 +175,7 @@ mod diff {
         // two files with the same content
         let mut file2 = NamedTempFile::new()?;
         file2.write_all("foo\n".as_bytes())?;
-        for option in ["", "-u", "-c", "-e"] {
+        for option in ["", "-u", "-c", "-e", "-n", "--format=json", "-q"] {
             let mut cmd = Command::cargo_bin("diffutils")?;
             cmd.arg("diff");
             if !option.is_empty() {
@@ -154,11 +186,162 @@ mod diff {
                 .code(predicate::eq(0))
                 .success()
                 .stdout(predicate::eq(format!(
-                    "Files {} and {} are identical\n",
+                    "{}Files {} and {} are identical\n",
+                    if option == "--format=json" {
+                        "[]\n"
+                    } else {
+                        ""
+                    },
                     file1.path().to_string_lossy(),
                     file2.path().to_string_lossy(),
                 )));
         }
+
+        // The message names the file actually compared inside a directory
+        let tmp_dir = tempdir()?;
+        let dir_file = tmp_dir.path().join("foo");
+        File::create(&dir_file)?.write_all(b"foo\n")?;
+        let file3 = tmp_dir.path().join("other").join("foo");
+        std::fs::create_dir(file3.parent().unwrap())?;
+        File::create(&file3)?.write_all(b"foo\n")?;
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("-s").arg(tmp_dir.path()).arg(&file3);
+        cmd.assert()
+            .code(predicate::eq(0))
+            .success()
+            .stdout(predicate::eq(format!(
+                "Files {} and {} are identical\n",
+                dir_file.to_string_lossy(),
+                file3.to_string_lossy(),
+            )));
+        Ok(())
+    }
+
+    #[test]
+    fn same_file_shortcut() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempdir()?;
+        let file = tmp_dir.path().join("-");
+        File::create(&file)?.write_all(b"foo\n")?;
+
+        // Another name for the same file is identical without being read
+        let link = tmp_dir.path().join("link");
+        std::fs::hard_link(&file, &link)?;
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("-s").arg(&file).arg(&link);
+        cmd.assert()
+            .code(predicate::eq(0))
+            .success()
+            .stdout(predicate::str::ends_with(" are identical\n"));
+
+        // but "-" is stdin, not the file named "-"
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.current_dir(tmp_dir.path());
+        cmd.arg("diff");
+        cmd.arg("-").arg("./-");
+        cmd.write_stdin("bar\n");
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq("1c1\n< bar\n---\n> foo\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn output() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempdir()?;
+        let file1 = tmp_dir.path().join("a");
+        File::create(&file1)?.write_all(b"foo\n")?;
+        let file2 = tmp_dir.path().join("b");
+        File::create(&file2)?.write_all(b"bar\n")?;
+        let out = tmp_dir.path().join("out");
+
+        for option in ["-o", "--output"] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.arg("diff")
+                .arg(option)
+                .arg(&out)
+                .arg(&file1)
+                .arg(&file2);
+            cmd.assert()
+                .code(predicate::eq(1))
+                .failure()
+                .stdout(predicate::str::is_empty());
+            assert_eq!(std::fs::read(&out)?, b"1c1\n< foo\n---\n> bar\n");
+        }
+
+        // An existing output file is truncated
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff")
+            .arg(format!("--output={}", out.to_string_lossy()))
+            .arg(&file1)
+            .arg(&file1);
+        cmd.assert()
+            .code(predicate::eq(0))
+            .success()
+            .stdout(predicate::str::is_empty());
+        assert_eq!(std::fs::read(&out)?, b"");
+
+        // "-" is stdout
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff").arg("-o").arg("-").arg(&file1).arg(&file2);
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq("1c1\n< foo\n---\n> bar\n"));
+
+        // An input is never overwritten
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff")
+            .arg("-o")
+            .arg(&file2)
+            .arg(&file1)
+            .arg(&file2);
+        cmd.assert()
+            .code(predicate::eq(2))
+            .failure()
+            .stderr(predicate::str::ends_with(
+                "output file is also an input file\n",
+            ));
+        assert_eq!(std::fs::read(&file2)?, b"bar\n");
+
+        // The file gets what stdout would, in every format
+        for format in ["-u", "-c", "-e", "-n"] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.args(["diff", format]).arg(&file1).arg(&file2);
+            let stdout = cmd.output()?.stdout;
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.args(["diff", format, "-o"]).arg(&out);
+            cmd.arg(&file1).arg(&file2);
+            cmd.assert().code(predicate::eq(1)).failure();
+            assert_eq!(std::fs::read(&out)?, stdout);
+        }
+
+        // Failing to open or write the file is trouble, and names it
+        let missing = tmp_dir.path().join("missing").join("out");
+        #[cfg(not(windows))]
+        let missing_error = "No such file or directory";
+        #[cfg(windows)]
+        let missing_error = "The system cannot find the path specified.";
+        let mut cases = vec![(missing.as_path(), missing_error)];
+        if cfg!(target_os = "linux") {
+            cases.push((Path::new("/dev/full"), "No space left on device"));
+        }
+        for (path, error) in cases {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.args(["diff", "-o"]).arg(path).arg(&file1).arg(&file2);
+            cmd.assert()
+                .code(predicate::eq(2))
+                .failure()
+                .stdout(predicate::str::is_empty())
+                .stderr(predicate::eq(format!(
+                    "diff: {}: {error}\n",
+                    path.to_string_lossy()
+                )));
+        }
+
         Ok(())
     }
 
@@ -183,6 +366,24 @@ mod diff {
         Ok(())
     }
 
+    #[test]
+    fn version() -> Result<(), Box<dyn std::error::Error>> {
+        for option in ["--version", "-v"] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.arg("diff");
+            cmd.arg(option);
+            cmd.assert()
+                .code(predicate::eq(0))
+                .success()
+                .stdout(predicate::str::starts_with(format!(
+                    "diff (uutils diffutils) {}\nLicense ",
+                    env!("CARGO_PKG_VERSION")
+                )));
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn differences_brief() -> Result<(), Box<dyn std::error::Error>> {
         let mut file1 = NamedTempFile::new()?;
@@ -208,6 +409,95 @@ mod diff {
         Ok(())
     }
 
+    #[test]
+    fn check() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file1 = NamedTempFile::new()?;
+        file1.write_all("foo\n".as_bytes())?;
+        let mut file2 = NamedTempFile::new()?;
+        file2.write_all("bar\n".as_bytes())?;
+        for option in ["", "-u", "-c", "-e", "-q", "-s", "--git", "--format=json"] {
+            for (to, code) in [(file2.path(), 1), (file1.path(), 0)] {
+                let mut cmd = Command::cargo_bin("diffutils")?;
+                cmd.arg("diff").arg("--check");
+                if !option.is_empty() {
+                    cmd.arg(option);
+                }
+                cmd.arg(file1.path()).arg(to);
+                cmd.assert()
+                    .code(predicate::eq(code))
+                    .stdout(predicate::str::is_empty())
+                    .stderr(predicate::str::is_empty());
+            }
+        }
+
+        // Binary inputs are not reported either
+        let mut file3 = NamedTempFile::new()?;
+        file3.write_all(b"\0foo\n")?;
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff")
+            .arg("--check")
+            .arg(file1.path())
+            .arg(file3.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .stdout(predicate::str::is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn differences_brief_missing_newline() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file1 = NamedTempFile::new()?;
+        file1.write_all("foo\n".as_bytes())?;
+        let mut file2 = NamedTempFile::new()?;
+        file2.write_all("foo".as_bytes())?;
+        for option in ["", "-u", "-c", "-e", "-n", "--format=json"] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.arg("diff");
+            if !option.is_empty() {
+                cmd.arg(option);
+            }
+            cmd.arg("-q").arg(file1.path()).arg(file2.path());
+            cmd.assert()
+                .code(predicate::eq(1))
+                .failure()
+                .stderr(predicate::str::is_empty())
+                .stdout(predicate::eq(format!(
+                    "Files {} and {} differ\n",
+                    file1.path().to_string_lossy(),
+                    file2.path().to_string_lossy()
+                )));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn differences_brief_pipe() -> Result<(), Box<dyn std::error::Error>> {
+        // A regular file against a pipe, differing well past the first
+        // buffer's worth of input
+        let contents: String = (0..100_000).map(|i| format!("{i}\n")).collect();
+        let mut file1 = NamedTempFile::new()?;
+        file1.write_all(contents.as_bytes())?;
+        let differ = format!("Files {} and - differ\n", file1.path().to_string_lossy());
+        for (stdin, code, stdout) in [
+            (
+                contents.replace("\n50000\n", "\n5000x\n"),
+                1,
+                differ.as_str(),
+            ),
+            (contents.clone(), 0, ""),
+        ] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.arg("diff").arg("-q").arg(file1.path()).arg("-");
+            cmd.write_stdin(stdin);
+            cmd.assert()
+                .code(predicate::eq(code))
+                .stderr(predicate::str::is_empty())
+                .stdout(predicate::eq(stdout));
+        }
+        Ok(())
+    }
+
     #[test]
     fn missing_newline() -> Result<(), Box<dyn std::error::Error>> {
         let mut file1 = NamedTempFile::new()?;
@@ -225,96 +515,983 @@ mod diff {
     }
 
     #[test]
-    fn read_from_stdin() -> Result<(), Box<dyn std::error::Error>> {
+    fn expand_tabs() -> Result<(), Box<dyn std::error::Error>> {
         let mut file1 = NamedTempFile::new()?;
-        file1.write_all("foo\n".as_bytes())?;
+        file1.write_all("\tfoo\n".as_bytes())?;
         let mut file2 = NamedTempFile::new()?;
-        file2.write_all("bar\n".as_bytes())?;
+        file2.write_all("\tbar\n".as_bytes())?;
 
         let mut cmd = Command::cargo_bin("diffutils")?;
         cmd.arg("diff");
-        cmd.arg("-u")
-            .arg(file1.path())
-            .arg("-")
-            .write_stdin("bar\n");
-        cmd.assert().code(predicate::eq(1)).failure();
+        cmd.arg(file1.path()).arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq("1c1\n< \tfoo\n---\n> \tbar\n"));
 
-        let output = cmd.output().unwrap().stdout;
-        assert_diff_eq!(
-            output,
-            format!(
-                "--- {}\tTIMESTAMP\n+++ -\tTIMESTAMP\n@@ -1 +1 @@\n-foo\n+bar\n",
-                file1.path().to_string_lossy()
-            )
-        );
+        // Tab stops default to every 8 columns, as in GNU diff
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("-t").arg(file1.path()).arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq("1c1\n<         foo\n---\n>         bar\n"));
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("-t")
+            .arg("--tabsize=4")
+            .arg(file1.path())
+            .arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq("1c1\n<     foo\n---\n>     bar\n"));
 
+        // The tab size also applies to the body of unified hunks
         let mut cmd = Command::cargo_bin("diffutils")?;
         cmd.arg("diff");
         cmd.arg("-u")
-            .arg("-")
-            .arg(file2.path())
-            .write_stdin("foo\n");
-        cmd.assert().code(predicate::eq(1)).failure();
+            .arg("-t")
+            .arg("--tabsize")
+            .arg("4")
+            .arg(file1.path())
+            .arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::str::ends_with(
+                "@@ -1 +1 @@\n-    foo\n+    bar\n",
+            ));
 
-        let output = cmd.output().unwrap().stdout;
-        assert_diff_eq!(
-            output,
-            format!(
-                "--- -\tTIMESTAMP\n+++ {}\tTIMESTAMP\n@@ -1 +1 @@\n-foo\n+bar\n",
-                file2.path().to_string_lossy()
-            )
-        );
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("--tabsize=0").arg(file1.path()).arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(2))
+            .failure()
+            .stderr(predicate::str::starts_with("invalid tabsize"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn strip_trailing_cr() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file1 = NamedTempFile::new()?;
+        file1.write_all("foo\r\nbar\r\n".as_bytes())?;
+        let mut file2 = NamedTempFile::new()?;
+        file2.write_all("foo\nbar\n".as_bytes())?;
 
         let mut cmd = Command::cargo_bin("diffutils")?;
         cmd.arg("diff");
-        cmd.arg("-u").arg("-").arg("-");
+        cmd.arg("--strip-trailing-cr")
+            .arg(file1.path())
+            .arg(file2.path());
         cmd.assert()
             .code(predicate::eq(0))
             .success()
             .stdout(predicate::str::is_empty());
 
-        #[cfg(unix)]
-        {
-            let mut cmd = Command::cargo_bin("diffutils")?;
-            cmd.arg("diff");
-            cmd.arg("-u")
-                .arg(file1.path())
-                .arg("/dev/stdin")
-                .write_stdin("bar\n");
-            cmd.assert().code(predicate::eq(1)).failure();
-
-            let output = cmd.output().unwrap().stdout;
-            assert_diff_eq!(
-                output,
-                format!(
-                    "--- {}\tTIMESTAMP\n+++ /dev/stdin\tTIMESTAMP\n@@ -1 +1 @@\n-foo\n+bar\n",
-                    file1.path().to_string_lossy()
-                )
-            );
-        }
+        // A carriage return is only stripped before a newline, so an
+        // unterminated line keeps it.
+        let mut file3 = NamedTempFile::new()?;
+        file3.write_all("foo\r\nbar\r".as_bytes())?;
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("--strip-trailing-cr")
+            .arg(file3.path())
+            .arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq(
+                "2c2\n< bar\r\n\\ No newline at end of file\n---\n> bar\n",
+            ));
 
         Ok(())
     }
 
     #[test]
-    fn compare_file_to_directory() -> Result<(), Box<dyn std::error::Error>> {
+    fn git() -> Result<(), Box<dyn std::error::Error>> {
+        if std::process::Command::new("git")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            return Ok(());
+        }
         let tmp_dir = tempdir()?;
-
-        let directory = tmp_dir.path().join("d");
-        let _ = std::fs::create_dir(&directory);
-
-        let a_path = tmp_dir.path().join("a");
-        let mut a = File::create(&a_path).unwrap();
-        a.write_all(b"a\n").unwrap();
-
-        let da_path = directory.join("a");
-        let mut da = File::create(&da_path).unwrap();
-        da.write_all(b"da\n").unwrap();
+        std::fs::write(tmp_dir.path().join("f"), "a\nb\nc\n")?;
+        std::fs::write(tmp_dir.path().join("f.new"), "a\nB\nc\nd\n")?;
+        std::fs::write(tmp_dir.path().join("n.new"), "new\n")?;
 
         let mut cmd = Command::cargo_bin("diffutils")?;
-        cmd.arg("diff");
-        cmd.arg("-u").arg(&directory).arg(&a_path);
-        cmd.assert().code(predicate::eq(1)).failure();
+        cmd.current_dir(tmp_dir.path());
+        cmd.args([
+            "diff", "--git", "--label", "f", "--label", "f", "f", "f.new",
+        ]);
+        let patch = cmd
+            .assert()
+            .code(predicate::eq(1))
+            .get_output()
+            .stdout
+            .clone();
+        assert!(patch.starts_with(b"diff --git a/f b/f\nindex de98044..a7bc997 100644\n"));
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.current_dir(tmp_dir.path());
+        cmd.args(["diff", "-G", "--unidirectional-new-file"]);
+        cmd.args(["--label", "n", "--label", "n", "n", "n.new"]);
+        let new_file_patch = cmd
+            .assert()
+            .code(predicate::eq(1))
+            .get_output()
+            .stdout
+            .clone();
+        assert!(new_file_patch.starts_with(
+            b"diff --git a/n b/n\nnew file mode 100644\nindex 0000000..3e75765\n--- /dev/null\n"
+        ));
+
+        // git accepts both patches and applies them
+        for patch in [patch, new_file_patch] {
+            for args in [&["apply", "--check"][..], &["apply"]] {
+                let mut git = std::process::Command::new("git");
+                git.current_dir(tmp_dir.path()).args(args);
+                let mut child = git.stdin(std::process::Stdio::piped()).spawn()?;
+                child.stdin.take().unwrap().write_all(&patch)?;
+                assert!(child.wait()?.success());
+            }
+        }
+        assert_eq!(std::fs::read(tmp_dir.path().join("f"))?, b"a\nB\nc\nd\n");
+        assert_eq!(std::fs::read(tmp_dir.path().join("n"))?, b"new\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn horizon_lines() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file1 = NamedTempFile::new()?;
+        file1.write_all(b"p\nx\na\ns\n")?;
+        let mut file2 = NamedTempFile::new()?;
+        file2.write_all(b"p\ny\na\nb\na\ns\n")?;
+
+        for (option, expected) in [
+            ("--horizon-lines=0", "2c2,4\n< x\n---\n> y\n> a\n> b\n"),
+            ("--horizon-lines=3", "2c2\n< x\n---\n> y\n3a4,5\n> b\n> a\n"),
+        ] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.args(["diff", option]);
+            cmd.arg(file1.path()).arg(file2.path());
+            cmd.assert()
+                .code(predicate::eq(1))
+                .failure()
+                .stdout(predicate::eq(expected));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn strip_paths() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempdir()?;
+        for dir in ["old/src", "new/src"] {
+            std::fs::create_dir_all(tmp_dir.path().join(dir))?;
+        }
+        std::fs::write(tmp_dir.path().join("old/src/f"), "foo\n")?;
+        std::fs::write(tmp_dir.path().join("new/src/f"), "bar\n")?;
+
+        for (option, from, to) in [
+            ("-u", "--- src/f\t", "+++ src/f\t"),
+            ("-c", "*** src/f\t", "--- src/f\t"),
+            ("--git", "--- a/src/f\n", "+++ b/src/f\n"),
+        ] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.current_dir(tmp_dir.path());
+            cmd.args(["diff", option, "--strip-paths=1", "old/src/f", "new/src/f"]);
+            cmd.assert()
+                .code(predicate::eq(1))
+                .failure()
+                .stdout(predicate::str::contains(from).and(predicate::str::contains(to)));
+        }
+
+        // A label wins over the stripped name
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.current_dir(tmp_dir.path());
+        cmd.args(["diff", "-u", "--strip-paths", "2", "--label", "x/y"]);
+        cmd.args(["old/src/f", "new/src/f"]);
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::str::starts_with("--- x/y\n+++ f\t"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn relative_to() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempdir()?;
+        for dir in ["old", "new"] {
+            std::fs::create_dir(tmp_dir.path().join(dir))?;
+        }
+        let from = tmp_dir.path().join("old/f");
+        let to = tmp_dir.path().join("new/f");
+        std::fs::write(&from, "foo\n")?;
+        std::fs::write(&to, "bar\n")?;
+
+        for (option, from_header, to_header) in [
+            ("-u", "--- old/f\t", "+++ new/f\t"),
+            ("--git", "--- a/old/f\n", "+++ b/new/f\n"),
+        ] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.args(["diff", option, "--relative-to"]);
+            cmd.arg(tmp_dir.path()).arg(&from).arg(&to);
+            cmd.assert().code(predicate::eq(1)).failure().stdout(
+                predicate::str::contains(from_header).and(predicate::str::contains(to_header)),
+            );
+        }
+
+        // Names outside the directory are shown as given
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.args(["diff", "-u", "--relative-to=/nonexistent"]);
+        cmd.arg(&from).arg(&to);
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::str::starts_with(format!(
+                "--- {}\t",
+                from.display()
+            )));
+
+        // The patch applies from the directory given
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.args(["diff", "-u"]);
+        cmd.arg(format!("--relative-to={}", tmp_dir.path().display()));
+        cmd.arg(&from).arg(&to);
+        let patch = cmd.output()?.stdout;
+        let mut child = std::process::Command::new("patch")
+            .current_dir(tmp_dir.path())
+            .args(["-s", "-p0"])
+            .stdin(std::process::Stdio::piped())
+            .spawn()?;
+        child.stdin.take().unwrap().write_all(&patch)?;
+        assert!(child.wait()?.success());
+        assert_eq!(std::fs::read(&from)?, b"bar\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn initial_tab() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file1 = NamedTempFile::new()?;
+        file1.write_all(b"a\nb\n\n")?;
+        let mut file2 = NamedTempFile::new()?;
+        file2.write_all(b"a\nc\n\n")?;
+
+        // As GNU diff writes them: the tab replaces the blank indicator of
+        // unified context lines, and the space after the others
+        for (options, expected) in [
+            (&[][..], "2c2\n<\tb\n---\n>\tc\n"),
+            (
+                &["-u", "--label=x", "--label=y"],
+                "--- x\n+++ y\n@@ -1,3 +1,3 @@\n\ta\n-\tb\n+\tc\n\t\n",
+            ),
+            (
+                &["-c", "--label=x", "--label=y"],
+                "*** x\n--- y\n***************\n*** 1,3 ****\n \ta\n!\tb\n \t\n\
+                 --- 1,3 ----\n \ta\n!\tc\n \t\n",
+            ),
+        ] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.args(["diff", "-T"]).args(options);
+            cmd.arg(file1.path()).arg(file2.path());
+            cmd.assert()
+                .code(predicate::eq(1))
+                .failure()
+                .stdout(predicate::eq(expected));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn ignore_bom() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file1 = NamedTempFile::new()?;
+        file1.write_all(b"\xEF\xBB\xBFfoo\nbar\n")?;
+        let mut file2 = NamedTempFile::new()?;
+        file2.write_all(b"foo\nbar\n")?;
+
+        // Without the flag the mark is a difference like any other
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff").arg(file1.path()).arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq(&b"1c1\n< \xEF\xBB\xBFfoo\n---\n> foo\n"[..]));
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.args(["diff", "--ignore-bom"]);
+        cmd.arg(file1.path()).arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(0))
+            .success()
+            .stdout(predicate::str::is_empty());
+
+        // Nor is it shown in other differences
+        let mut file3 = NamedTempFile::new()?;
+        file3.write_all(b"foo\nbaz\n")?;
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.args(["diff", "-U1", "--ignore-bom", "--label=a", "--label=b"]);
+        cmd.arg(file1.path()).arg(file3.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq(
+                "--- a\n+++ b\n@@ -1,2 +1,2 @@\n foo\n-bar\n+baz\n",
+            ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn skip() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file1 = NamedTempFile::new()?;
+        file1.write_all("HEADER 1\nfoo\nbar\n".as_bytes())?;
+        let mut file2 = NamedTempFile::new()?;
+        file2.write_all("HEADER 2\nfoo\nbaz\n".as_bytes())?;
+
+        // Line numbers count from the first byte kept
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff")
+            .arg("--skip=9")
+            .arg(file1.path())
+            .arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq("2c2\n< bar\n---\n> baz\n"));
+
+        // The skip may end mid-line
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff")
+            .arg("--skip=7")
+            .arg(file1.path())
+            .arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq(
+                "1c1\n< 1\n---\n> 2\n3c3\n< bar\n---\n> baz\n",
+            ));
+
+        // Skipping past the end of both leaves nothing to compare
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff")
+            .arg("--skip=1K")
+            .arg(file1.path())
+            .arg(file2.path());
+        cmd.assert().code(predicate::eq(0)).success();
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff")
+            .arg("--skip=9Q")
+            .arg(file1.path())
+            .arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(2))
+            .failure()
+            .stderr(predicate::str::starts_with("invalid --skip value '9Q'"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn label() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file1 = NamedTempFile::new()?;
+        file1.write_all("foo\n".as_bytes())?;
+        let mut file2 = NamedTempFile::new()?;
+        file2.write_all("bar\n".as_bytes())?;
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.args(["-u", "--label", "a", "--label=b"])
+            .arg(file1.path())
+            .arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq("--- a\n+++ b\n@@ -1 +1 @@\n-foo\n+bar\n"));
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.args(["--label", "a", "--label", "b", "--label", "c"])
+            .arg(file1.path())
+            .arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(2))
+            .failure()
+            .stderr(predicate::str::contains("too many file label options"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn text() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file1 = NamedTempFile::new()?;
+        file1.write_all(b"a\0b\nc\n")?;
+        let mut file2 = NamedTempFile::new()?;
+        file2.write_all(b"a\0b\nd\n")?;
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg(file1.path()).arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq(format!(
+                "Binary files {} and {} differ\n",
+                file1.path().to_string_lossy(),
+                file2.path().to_string_lossy()
+            )));
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("-q").arg(file1.path()).arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::str::starts_with("Files "));
+
+        // Identical binary files are not reported
+        let mut file3 = NamedTempFile::new()?;
+        file3.write_all(b"a\0b\nc\n")?;
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg(file1.path()).arg(file3.path());
+        cmd.assert()
+            .code(predicate::eq(0))
+            .success()
+            .stdout(predicate::str::is_empty());
+
+        // With -a, NUL bytes go through to the output unmodified
+        for option in ["-a", "--text"] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.arg("diff");
+            cmd.arg(option).arg(file1.path()).arg(file2.path());
+            cmd.assert()
+                .code(predicate::eq(1))
+                .failure()
+                .stdout(predicate::eq("2c2\n< c\n---\n> d\n"));
+        }
+        let mut file4 = NamedTempFile::new()?;
+        file4.write_all(b"a\0B\nc\n")?;
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("-a").arg(file1.path()).arg(file4.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq("1c1\n< a\0b\n---\n> a\0B\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn binary_crlf() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file1 = NamedTempFile::new()?;
+        file1.write_all("foo\r\nbar\r\n".as_bytes())?;
+        let mut file2 = NamedTempFile::new()?;
+        file2.write_all("foo\nbar\n".as_bytes())?;
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("--binary").arg(file1.path()).arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq(
+                "1,2c1,2\n< foo\r\n< bar\r\n---\n> foo\n> bar\n",
+            ));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn label_not_utf8() -> Result<(), Box<dyn std::error::Error>> {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let mut file1 = NamedTempFile::new()?;
+        file1.write_all("foo\n".as_bytes())?;
+        let mut file2 = NamedTempFile::new()?;
+        file2.write_all("bar\n".as_bytes())?;
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("-c")
+            .arg("--label")
+            .arg(OsStr::from_bytes(b"caf\xe9"))
+            .arg(OsStr::from_bytes(b"--label=\xff\xfe"))
+            .arg(file1.path())
+            .arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::function(|output: &[u8]| {
+                output.starts_with(b"*** caf\xe9\n--- \xff\xfe\n")
+            }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn unidirectional_new_file() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = NamedTempFile::new()?;
+        file.write_all("foo\n".as_bytes())?;
+        let tmp_dir = tempdir()?;
+        let absent = tmp_dir.path().join("absent");
+
+        // An absent first file is treated as empty
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("--unidirectional-new-file")
+            .arg(&absent)
+            .arg(file.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stderr(predicate::str::is_empty())
+            .stdout(predicate::eq("0a1\n> foo\n"));
+
+        // also when the second input is stdin
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("--unidirectional-new-file").arg(&absent).arg("-");
+        cmd.write_stdin("foo\n");
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stderr(predicate::str::is_empty())
+            .stdout(predicate::eq("0a1\n> foo\n"));
+
+        // but an absent second file is still an error
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("--unidirectional-new-file")
+            .arg(file.path())
+            .arg(&absent);
+        cmd.assert()
+            .code(predicate::eq(2))
+            .failure()
+            .stderr(predicate::str::contains("No such file or directory"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn color_precedence() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file1 = NamedTempFile::new()?;
+        file1.write_all("foo\n".as_bytes())?;
+        let mut file2 = NamedTempFile::new()?;
+        file2.write_all("bar\n".as_bytes())?;
+
+        // (option, NO_COLOR, CLICOLOR_FORCE, colored?)
+        for (option, no_color, clicolor_force, colored) in [
+            ("--color=never", None, Some("1"), false),
+            ("--no-color", None, Some("1"), false),
+            ("--color=auto", None, Some("1"), true),
+            ("--color", None, Some("0"), false),
+            ("--color", Some("1"), Some("1"), false),
+            ("--color=always", Some("1"), None, true),
+        ] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.env_remove("NO_COLOR").env_remove("CLICOLOR_FORCE");
+            if let Some(value) = no_color {
+                cmd.env("NO_COLOR", value);
+            }
+            if let Some(value) = clicolor_force {
+                cmd.env("CLICOLOR_FORCE", value);
+            }
+            cmd.arg("diff");
+            cmd.arg("-u")
+                .arg(option)
+                .arg(file1.path())
+                .arg(file2.path());
+            let escape = predicate::str::contains("\x1b[");
+            if colored {
+                cmd.assert().code(predicate::eq(1)).stdout(escape);
+            } else {
+                cmd.assert().code(predicate::eq(1)).stdout(escape.not());
+            }
+        }
+
+        Ok(())
+    }
+
+    // --color=auto only colors what goes straight to the terminal. This runs
+    // diff on a pseudo-terminal with script(1), where it is available.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn color_auto_terminal() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempdir()?;
+        std::fs::write(tmp_dir.path().join("a"), "foo\n")?;
+        std::fs::write(tmp_dir.path().join("b"), "bar\n")?;
+        let diff = assert_cmd::cargo::cargo_bin("diffutils");
+        let on_terminal = |args: &str| -> std::io::Result<Option<Vec<u8>>> {
+            let command = format!("'{}' diff --color=auto -u {args} a b", diff.display());
+            let output = std::process::Command::new("script")
+                .current_dir(tmp_dir.path())
+                .env_remove("NO_COLOR")
+                .env_remove("CLICOLOR_FORCE")
+                .args(["-qec", &command, "/dev/null"])
+                .output();
+            match output {
+                Ok(output) => Ok(Some(output.stdout)),
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(error) => Err(error),
+            }
+        };
+        let has_color = |output: &[u8]| output.windows(2).any(|w| w == b"\x1b[");
+
+        let Some(stdout) = on_terminal("")? else {
+            return Ok(());
+        };
+        assert!(has_color(&stdout));
+
+        let stdout = on_terminal("-o out")?.unwrap();
+        assert!(stdout.is_empty());
+        let out = std::fs::read(tmp_dir.path().join("out"))?;
+        assert!(out.starts_with(b"--- a\t"));
+        assert!(!has_color(&out));
+
+        let stdout = on_terminal("-l --pager=cat")?.unwrap();
+        assert!(stdout.starts_with(b"--- a\t"));
+        assert!(!has_color(&stdout));
+
+        Ok(())
+    }
+
+    #[test]
+    fn palette() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file1 = NamedTempFile::new()?;
+        file1.write_all("foo\n".as_bytes())?;
+        let mut file2 = NamedTempFile::new()?;
+        file2.write_all("bar\n".as_bytes())?;
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.args(["diff", "-u", "--color=always", "--palette=ad=1;34:zz=1"])
+            .arg(file1.path())
+            .arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .stdout(predicate::str::ends_with(
+                "\x1b[31m-foo\x1b[0m\n\x1b[1;34m+bar\x1b[0m\n",
+            ))
+            .stderr(predicate::str::contains("unknown palette key 'zz'"));
+
+        // The normal and context formats are colored too, the others not
+        for (option, expected) in [
+            ("--normal", "---\n\x1b[1;34m> bar\x1b[0m\n"),
+            ("-c", "\x1b[36m--- 1 ----\x1b[0m\n\x1b[1;34m! bar\x1b[0m\n"),
+            ("-e", "1c\nbar\n.\n"),
+        ] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.args(["diff", option, "--color=always", "--palette=ad=1;34"])
+                .arg(file1.path())
+                .arg(file2.path());
+            cmd.assert()
+                .code(predicate::eq(1))
+                .stdout(predicate::str::ends_with(expected));
+        }
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.args(["diff", "-u", "--color=always", "--palette=ad=green"])
+            .arg(file1.path())
+            .arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(2))
+            .stdout(predicate::str::is_empty())
+            .stderr(predicate::str::starts_with("invalid color code 'green'"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn posixly_correct() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file1 = NamedTempFile::new()?;
+        file1.write_all("foo\n".as_bytes())?;
+        let mut file2 = NamedTempFile::new()?;
+        file2.write_all("bar\n".as_bytes())?;
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env_remove("POSIXLY_CORRECT");
+        cmd.arg("diff");
+        cmd.arg(file1.path()).arg("-u").arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::str::starts_with("--- "));
+
+        // With POSIXLY_CORRECT, -u is the second operand and the third
+        // operand is one too many.
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("POSIXLY_CORRECT", "1");
+        cmd.arg("diff");
+        cmd.arg(file1.path()).arg("-u").arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(2))
+            .failure()
+            .stdout(predicate::str::is_empty())
+            .stderr(predicate::str::starts_with("Usage: diff"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn paginate() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file1 = NamedTempFile::new()?;
+        file1.write_all("foo\n".as_bytes())?;
+        let mut file2 = NamedTempFile::new()?;
+        file2.write_all("bar\n".as_bytes())?;
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env_remove("PR");
+        cmd.arg("diff");
+        cmd.arg("-l")
+            .arg("--pager=cat")
+            .arg(file1.path())
+            .arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq("1c1\n< foo\n---\n> bar\n"));
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env_remove("PR");
+        cmd.args(["diff", "-l", "--pager", "cat"]);
+        cmd.arg(file1.path()).arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq("1c1\n< foo\n---\n> bar\n"));
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("PR", "cat");
+        cmd.arg("diff");
+        cmd.arg("--paginate").arg(file1.path()).arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq("1c1\n< foo\n---\n> bar\n"));
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("-l")
+            .arg("--pager=this-pager-does-not-exist")
+            .arg(file1.path())
+            .arg(file2.path());
+        #[cfg(not(windows))]
+        let error_message = "No such file or directory";
+        #[cfg(windows)]
+        let error_message = "The system cannot find the file specified.";
+        cmd.assert()
+            .code(predicate::eq(2))
+            .failure()
+            .stdout(predicate::str::is_empty())
+            .stderr(predicate::eq(format!(
+                "diff: subsidiary program 'this-pager-does-not-exist' could not be invoked: \
+                 {error_message}\n"
+            )));
+
+        // A pager that fails is trouble too, even when it quits before the
+        // diff is written, so that writing it fails as well
+        #[cfg(unix)]
+        {
+            let mut large = NamedTempFile::new()?;
+            large.write_all("baz\n".repeat(1 << 18).as_bytes())?;
+            for to in [file2.path(), large.path()] {
+                let mut cmd = Command::cargo_bin("diffutils")?;
+                cmd.args(["diff", "-l", "--pager=false"]);
+                cmd.arg(file1.path()).arg(to);
+                cmd.assert()
+                    .code(predicate::eq(2))
+                    .failure()
+                    .stderr(predicate::eq(
+                        "diff: subsidiary program 'false' failed (exit status 1)\n",
+                    ));
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn color_function_context() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file1 = NamedTempFile::new()?;
+        file1.write_all("void f()\n{\n  a();\n}\n".as_bytes())?;
+        let mut file2 = NamedTempFile::new()?;
+        file2.write_all("void f()\n{\n  b();\n}\n".as_bytes())?;
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("-U1")
+            .arg("-p")
+            .arg("--color=always")
+            .arg(file1.path())
+            .arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::str::contains(
+                "\n\x1b[36m@@ -2,3 +2,3 @@\x1b[0m \x1b[2mvoid f()\x1b[0m\n",
+            ));
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("-U1")
+            .arg("-p")
+            .arg("--color=never")
+            .arg(file1.path())
+            .arg(file2.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::str::contains("\n@@ -2,3 +2,3 @@ void f()\n"))
+            .stdout(predicate::str::contains("\x1b").not());
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_from_stdin() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file1 = NamedTempFile::new()?;
+        file1.write_all("foo\n".as_bytes())?;
+        let mut file2 = NamedTempFile::new()?;
+        file2.write_all("bar\n".as_bytes())?;
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("-u")
+            .arg(file1.path())
+            .arg("-")
+            .write_stdin("bar\n");
+        cmd.assert().code(predicate::eq(1)).failure();
+
+        let output = cmd.output().unwrap().stdout;
+        assert_diff_eq!(
+            output,
+            format!(
+                "--- {}\tTIMESTAMP\n+++ -\tTIMESTAMP\n@@ -1 +1 @@\n-foo\n+bar\n",
+                file1.path().to_string_lossy()
+            )
+        );
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("-u")
+            .arg("-")
+            .arg(file2.path())
+            .write_stdin("foo\n");
+        cmd.assert().code(predicate::eq(1)).failure();
+
+        let output = cmd.output().unwrap().stdout;
+        assert_diff_eq!(
+            output,
+            format!(
+                "--- -\tTIMESTAMP\n+++ {}\tTIMESTAMP\n@@ -1 +1 @@\n-foo\n+bar\n",
+                file2.path().to_string_lossy()
+            )
+        );
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("-u").arg("-").arg("-");
+        cmd.assert()
+            .code(predicate::eq(0))
+            .success()
+            .stdout(predicate::str::is_empty());
+
+        #[cfg(unix)]
+        {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.arg("diff");
+            cmd.arg("-u")
+                .arg(file1.path())
+                .arg("/dev/stdin")
+                .write_stdin("bar\n");
+            cmd.assert().code(predicate::eq(1)).failure();
+
+            let output = cmd.output().unwrap().stdout;
+            assert_diff_eq!(
+                output,
+                format!(
+                    "--- {}\tTIMESTAMP\n+++ /dev/stdin\tTIMESTAMP\n@@ -1 +1 @@\n-foo\n+bar\n",
+                    file1.path().to_string_lossy()
+                )
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn cannot_read_one_file() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = NamedTempFile::new()?;
+        file.write_all("foo\n".as_bytes())?;
+
+        let nofile = NamedTempFile::new()?;
+        let nopath = nofile.into_temp_path();
+        std::fs::remove_file(&nopath)?;
+
+        #[cfg(not(windows))]
+        let error_message = "No such file or directory";
+        #[cfg(windows)]
+        let error_message = "The system cannot find the file specified.";
+
+        for (from, to) in [
+            (nopath.as_os_str(), file.path().as_os_str()),
+            (file.path().as_os_str(), nopath.as_os_str()),
+        ] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.arg("diff");
+            cmd.arg(from).arg(to);
+            cmd.assert()
+                .code(predicate::eq(2))
+                .failure()
+                .stdout(predicate::str::is_empty())
+                .stderr(
+                    predicate::str::contains(format!(
+                        ": {}: {error_message}\n",
+                        &nopath.as_os_str().to_string_lossy()
+                    ))
+                    .count(1)
+                    .and(predicate::str::contains(error_message).count(1)),
+                );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn compare_file_to_directory() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempdir()?;
+
+        let directory = tmp_dir.path().join("d");
+        let _ = std::fs::create_dir(&directory);
+
+        let a_path = tmp_dir.path().join("a");
+        let mut a = File::create(&a_path).unwrap();
+        a.write_all(b"a\n").unwrap();
+
+        let da_path = directory.join("a");
+        let mut da = File::create(&da_path).unwrap();
+        da.write_all(b"da\n").unwrap();
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("diff");
+        cmd.arg("-u").arg(&directory).arg(&a_path);
+        cmd.assert().code(predicate::eq(1)).failure();
 
         let output = cmd.output().unwrap().stdout;
         assert_diff_eq!(
@@ -327,56 +1504,745 @@ mod diff {
         );
 
         let mut cmd = Command::cargo_bin("diffutils")?;
-        cmd.arg("diff");
-        cmd.arg("-u").arg(&a_path).arg(&directory);
-        cmd.assert().code(predicate::eq(1)).failure();
+        cmd.arg("diff");
+        cmd.arg("-u").arg(&a_path).arg(&directory);
+        cmd.assert().code(predicate::eq(1)).failure();
+
+        let output = cmd.output().unwrap().stdout;
+        assert_diff_eq!(
+            output,
+            format!(
+                "--- {}\tTIMESTAMP\n+++ {}\tTIMESTAMP\n@@ -1 +1 @@\n-a\n+da\n",
+                a_path.display(),
+                da_path.display()
+            )
+        );
+
+        Ok(())
+    }
+}
+
+mod cmp {
+    use super::*;
+
+    #[test]
+    fn cmp_incompatible_params() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("cmp");
+        cmd.arg("-l");
+        cmd.arg("-s");
+        cmd.arg("/etc/passwd").arg("/etc/group");
+        cmd.assert()
+            .code(predicate::eq(2))
+            .failure()
+            .stderr(predicate::str::ends_with(
+                ": options -l and -s are incompatible\n",
+            ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cmp_help_after_operands() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempdir()?;
+
+        let a_path = tmp_dir.path().join("a");
+        let mut a = File::create(&a_path).unwrap();
+        a.write_all(b"a\n").unwrap();
+
+        let b_path = tmp_dir.path().join("b");
+        let mut b = File::create(&b_path).unwrap();
+        b.write_all(b"b\n").unwrap();
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env_remove("POSIXLY_CORRECT");
+        cmd.arg("cmp");
+        cmd.arg(&a_path).arg(&b_path).arg("--help");
+        cmd.assert()
+            .code(predicate::eq(0))
+            .success()
+            .stdout(predicate::str::starts_with("Usage: cmp"));
+
+        // With POSIXLY_CORRECT, option parsing stops at the first operand, so
+        // --help is taken as a skip operand.
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("POSIXLY_CORRECT", "1");
+        cmd.arg("cmp");
+        cmd.arg(&a_path).arg(&b_path).arg("--help");
+        cmd.assert()
+            .code(predicate::eq(2))
+            .failure()
+            .stdout(predicate::str::is_empty())
+            .stderr(predicate::str::ends_with(
+                ": invalid --ignore-initial value '--help'\n",
+            ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cmp_help() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("cmp");
+        cmd.arg("--help");
+        cmd.assert()
+            .code(predicate::eq(0))
+            .success()
+            .stdout(predicate::str::starts_with(
+                "Usage: cmp [OPTION]... FILE1 [FILE2 [SKIP1 [SKIP2]]]\n",
+            ))
+            .stdout(predicate::str::contains("  -b, --print-bytes "))
+            .stdout(predicate::str::contains(
+                "  -i, --ignore-initial=SKIP1:SKIP2 ",
+            ))
+            .stdout(predicate::str::contains("  -l, --verbose "))
+            .stdout(predicate::str::contains("  -n, --bytes=LIMIT "))
+            .stdout(predicate::str::contains("  -s, --quiet, --silent "))
+            .stdout(predicate::str::contains("  -T, --total "))
+            .stdout(predicate::str::contains("      --strip-trailing-cr "))
+            .stdout(predicate::str::contains(
+                "The optional SKIP1 and SKIP2 specify the number of bytes to skip\n",
+            ))
+            .stdout(predicate::str::contains("kB 1000, K 1024, MB 1,000,000"))
+            .stdout(predicate::str::ends_with(
+                "Exit status is 0 if inputs are the same, 1 if different, 2 if trouble.\n",
+            ));
+
+        // Usage errors show the same synopsis
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("cmp");
+        cmd.arg("a").arg("b").arg("1").arg("2").arg("3");
+        cmd.assert()
+            .code(predicate::eq(2))
+            .failure()
+            .stderr(predicate::str::starts_with(
+                "Usage: cmp [OPTION]... FILE1 [FILE2 [SKIP1 [SKIP2]]]",
+            ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cmp_version() -> Result<(), Box<dyn std::error::Error>> {
+        for option in ["--version", "-v"] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.arg("cmp");
+            cmd.arg(option);
+            cmd.assert()
+                .code(predicate::eq(0))
+                .success()
+                .stdout(predicate::str::starts_with(format!(
+                    "cmp (uutils diffutils) {}\nLicense ",
+                    env!("CARGO_PKG_VERSION")
+                )));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn cmp_posixly_correct() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempdir()?;
+
+        let a_path = tmp_dir.path().join("a");
+        let mut a = File::create(&a_path).unwrap();
+        a.write_all(b"a\n").unwrap();
+
+        let b_path = tmp_dir.path().join("b");
+        let mut b = File::create(&b_path).unwrap();
+        b.write_all(b"b\n").unwrap();
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env_remove("POSIXLY_CORRECT");
+        cmd.env("LC_ALL", "C");
+        cmd.arg("cmp");
+        cmd.arg(&a_path).arg("-b").arg(&b_path);
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::str::ends_with(" is 141 a 142 b\n"));
+
+        // With POSIXLY_CORRECT, -b is the second operand and the path of b
+        // is taken as the first skip value.
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("POSIXLY_CORRECT", "1");
+        cmd.arg("cmp");
+        cmd.arg(&a_path).arg("-b").arg(&b_path);
+        cmd.assert()
+            .code(predicate::eq(2))
+            .failure()
+            .stdout(predicate::str::is_empty())
+            .stderr(predicate::str::contains("invalid --ignore-initial value"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cmp_stdin() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempdir()?;
+
+        let a_path = tmp_dir.path().join("a");
+        let mut a = File::create(&a_path).unwrap();
+        a.write_all(b"a\n").unwrap();
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("cmp");
+        cmd.arg(&a_path);
+        cmd.write_stdin("a\n");
+        cmd.assert()
+            .code(predicate::eq(0))
+            .success()
+            .stderr(predicate::str::is_empty())
+            .stdout(predicate::str::is_empty());
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("LC_ALL", "C");
+        cmd.arg("cmp");
+        cmd.arg(&a_path);
+        cmd.write_stdin("b\n");
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stderr(predicate::str::is_empty())
+            .stdout(predicate::str::ends_with(" - differ: char 1, line 1\n"));
+
+        // Skipped stdin bytes are read and discarded. As in GNU cmp, bytes
+        // and lines are counted from the end of the skip, so the newlines
+        // skipped over do not count.
+        let b_path = tmp_dir.path().join("b");
+        let mut b = File::create(&b_path).unwrap();
+        b.write_all(b"a\nb\nc\nYd\n").unwrap();
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("LC_ALL", "C");
+        cmd.arg("cmp");
+        cmd.arg("-i").arg("2");
+        cmd.arg("-").arg(&b_path);
+        cmd.write_stdin("a\nb\nc\nXd\n");
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stderr(predicate::str::is_empty())
+            .stdout(predicate::str::ends_with(" differ: char 5, line 3\n"));
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("LC_ALL", "C");
+        cmd.arg("cmp");
+        cmd.arg("-i").arg("5:2");
+        cmd.arg("-").arg(&b_path);
+        cmd.write_stdin("zz\na\nb\nc\nXd\n");
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stderr(predicate::str::is_empty())
+            .stdout(predicate::str::ends_with(" differ: char 5, line 3\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cmp_equal_files() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempdir()?;
+
+        let a_path = tmp_dir.path().join("a");
+        let mut a = File::create(&a_path).unwrap();
+        a.write_all(b"a\n").unwrap();
+
+        let b_path = tmp_dir.path().join("b");
+        let mut b = File::create(&b_path).unwrap();
+        b.write_all(b"a\n").unwrap();
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("cmp");
+        cmd.arg(&a_path).arg(&b_path);
+        cmd.assert()
+            .code(predicate::eq(0))
+            .success()
+            .stderr(predicate::str::is_empty())
+            .stdout(predicate::str::is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn cmp_one_file_empty() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempdir()?;
+
+        let a_path = tmp_dir.path().join("a");
+        let mut a = File::create(&a_path).unwrap();
+        a.write_all(b"a\n").unwrap();
+
+        let b_path = tmp_dir.path().join("b");
+        let _ = File::create(&b_path).unwrap();
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("cmp");
+        cmd.arg(&a_path).arg(&b_path);
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stderr(predicate::str::contains(" EOF on "))
+            .stderr(predicate::str::ends_with(" which is empty\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cmp_eof_on_shorter_file() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempdir()?;
+
+        let short_path = tmp_dir.path().join("short");
+        let mut short = File::create(&short_path).unwrap();
+        short.write_all(b"abc\ndef").unwrap();
+
+        let long_path = tmp_dir.path().join("long");
+        let mut long = File::create(&long_path).unwrap();
+        long.write_all(b"abc\ndefgh\n").unwrap();
+
+        let short_str = short_path.to_string_lossy();
+        for (a, b) in [(&short_path, &long_path), (&long_path, &short_path)] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.arg("cmp");
+            cmd.arg(a).arg(b);
+            cmd.assert()
+                .code(predicate::eq(1))
+                .failure()
+                .stdout(predicate::str::is_empty())
+                .stderr(predicate::str::ends_with(format!(
+                    ": EOF on '{short_str}' after byte 7, in line 2\n"
+                )));
+
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.arg("cmp");
+            cmd.arg("-l").arg(a).arg(b);
+            cmd.assert()
+                .code(predicate::eq(1))
+                .failure()
+                .stdout(predicate::str::is_empty())
+                .stderr(predicate::str::ends_with(format!(
+                    ": EOF on '{short_str}' after byte 7\n"
+                )));
+
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.arg("cmp");
+            cmd.arg("-s").arg(a).arg(b);
+            cmd.assert()
+                .code(predicate::eq(1))
+                .failure()
+                .stdout(predicate::str::is_empty())
+                .stderr(predicate::str::is_empty());
+        }
+
+        // The shorter file ends at a line boundary.
+        let mut short = File::create(&short_path).unwrap();
+        short.write_all(b"abc\n").unwrap();
+        for (a, b) in [(&short_path, &long_path), (&long_path, &short_path)] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.arg("cmp");
+            cmd.arg(a).arg(b);
+            cmd.assert()
+                .code(predicate::eq(1))
+                .failure()
+                .stderr(predicate::str::ends_with(format!(
+                    ": EOF on '{short_str}' after byte 4, line 1\n"
+                )));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn cmp_crlf_line_count() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempdir()?;
+
+        let a_path = tmp_dir.path().join("a");
+        let mut a = File::create(&a_path).unwrap();
+        a.write_all(b"a\r\nb\r\nc\r\n").unwrap();
+
+        let b_path = tmp_dir.path().join("b");
+        let mut b = File::create(&b_path).unwrap();
+        b.write_all(b"a\r\nb\r\nd\r\n").unwrap();
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("LC_ALL", "C");
+        cmd.arg("cmp");
+        cmd.arg(&a_path).arg(&b_path);
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::str::ends_with(" differ: char 7, line 3\n"));
 
-        let output = cmd.output().unwrap().stdout;
-        assert_diff_eq!(
-            output,
-            format!(
-                "--- {}\tTIMESTAMP\n+++ {}\tTIMESTAMP\n@@ -1 +1 @@\n-a\n+da\n",
-                a_path.display(),
-                da_path.display()
-            )
-        );
+        // Enough equal CRLF lines to go through the block comparison fast path.
+        let lines = "line\r\n".repeat(3000);
+
+        let mut a = File::create(&a_path).unwrap();
+        a.write_all(lines.as_bytes()).unwrap();
+        a.write_all(b"A\r\n").unwrap();
+
+        let mut b = File::create(&b_path).unwrap();
+        b.write_all(lines.as_bytes()).unwrap();
+        b.write_all(b"B\r\n").unwrap();
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("LC_ALL", "C");
+        cmd.arg("cmp");
+        cmd.arg(&a_path).arg(&b_path);
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::str::ends_with(
+                " differ: char 18001, line 3001\n",
+            ));
 
         Ok(())
     }
-}
-
-mod cmp {
-    use super::*;
 
     #[test]
-    fn cmp_incompatible_params() -> Result<(), Box<dyn std::error::Error>> {
+    fn cmp_immediate_difference() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempdir()?;
+
+        let a_path = tmp_dir.path().join("a");
+        let mut a = File::create(&a_path).unwrap();
+        a.write_all(b"abc\n").unwrap();
+
+        let b_path = tmp_dir.path().join("b");
+        let mut b = File::create(&b_path).unwrap();
+        b.write_all(b"bcd\n").unwrap();
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("LC_ALL", "C");
+        cmd.arg("cmp");
+        cmd.arg(&a_path).arg(&b_path);
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::str::ends_with(" differ: char 1, line 1\n"));
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("LC_ALL", "C");
+        cmd.arg("cmp");
+        cmd.arg("-b");
+        cmd.arg(&a_path).arg(&b_path);
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stderr(predicate::str::is_empty())
+            .stdout(predicate::str::ends_with(
+                " differ: byte 1, line 1 is 141 a 142 b\n",
+            ));
+
         let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("LC_ALL", "C");
         cmd.arg("cmp");
         cmd.arg("-l");
-        cmd.arg("-s");
-        cmd.arg("/etc/passwd").arg("/etc/group");
+        cmd.arg(&a_path).arg(&b_path);
         cmd.assert()
-            .code(predicate::eq(2))
+            .code(predicate::eq(1))
             .failure()
-            .stderr(predicate::str::ends_with(
-                ": options -l and -s are incompatible\n",
+            .stderr(predicate::str::is_empty())
+            .stdout(predicate::eq("1 141 142\n2 142 143\n3 143 144\n"));
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("LC_ALL", "C");
+        cmd.arg("cmp");
+        cmd.arg("-l");
+        cmd.arg("-b");
+        cmd.arg(&a_path).arg(&b_path);
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stderr(predicate::str::is_empty())
+            .stdout(predicate::eq(
+                "1 141 a    142 b\n2 142 b    143 c\n3 143 c    144 d\n",
+            ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cmp_newline_difference() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempdir()?;
+
+        let a_path = tmp_dir.path().join("a");
+        let mut a = File::create(&a_path).unwrap();
+        a.write_all(b"abc\ndefg").unwrap();
+
+        let b_path = tmp_dir.path().join("b");
+        let mut b = File::create(&b_path).unwrap();
+        b.write_all(b"abc\ndef\ng").unwrap();
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("LC_ALL", "C");
+        cmd.arg("cmp");
+        cmd.arg(&a_path).arg(&b_path);
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stderr(predicate::str::is_empty())
+            .stdout(predicate::str::ends_with(" differ: char 8, line 2\n"));
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("LC_ALL", "C");
+        cmd.arg("cmp");
+        cmd.arg("-b");
+        cmd.arg(&a_path).arg(&b_path);
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stderr(predicate::str::is_empty())
+            .stdout(predicate::str::ends_with(
+                " differ: byte 8, line 2 is 147 g  12 ^J\n",
             ));
 
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("LC_ALL", "C");
+        cmd.arg("cmp");
+        cmd.arg("-l");
+        cmd.arg(&a_path).arg(&b_path);
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::str::starts_with("8 147  12\n"))
+            .stderr(predicate::str::contains(" EOF on"))
+            .stderr(predicate::str::ends_with(" after byte 8\n"));
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("LC_ALL", "C");
+        cmd.arg("cmp");
+        cmd.arg("-b");
+        cmd.arg("-l");
+        cmd.arg(&a_path).arg(&b_path);
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::str::starts_with("8 147 g     12 ^J\n"))
+            .stderr(predicate::str::contains(" EOF on"))
+            .stderr(predicate::str::ends_with(" after byte 8\n"));
+
+        // The last difference is on the last byte of the shorter file: every
+        // row is printed to stdout before the EOF message goes to stderr.
+        let c_path = tmp_dir.path().join("c");
+        let mut c = File::create(&c_path).unwrap();
+        c.write_all(b"axcx").unwrap();
+
+        let d_path = tmp_dir.path().join("d");
+        let mut d = File::create(&d_path).unwrap();
+        d.write_all(b"abcde\n").unwrap();
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("LC_ALL", "C");
+        cmd.arg("cmp");
+        cmd.arg("-l");
+        cmd.arg("-b");
+        cmd.arg(&c_path).arg(&d_path);
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq("2 170 x    142 b\n4 170 x    144 d\n"))
+            .stderr(predicate::str::contains(" EOF on"))
+            .stderr(predicate::str::ends_with(" after byte 4\n"));
+
+        Ok(())
+    }
+
+    // Files in /proc report a size of 0 but have content, so the offset
+    // width sized from their metadata can be narrower than the offsets.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn cmp_verbose_size_smaller_than_content() -> Result<(), Box<dyn std::error::Error>> {
+        let proc_file = "/proc/cpuinfo";
+        let mut contents = std::fs::read(proc_file)?;
+        if contents.len() < 200 {
+            return Ok(());
+        }
+        contents.truncate(200);
+        let from_byte = contents[50];
+        contents[50] = if from_byte == b'Z' { b'Y' } else { b'Z' };
+        let mut copy = NamedTempFile::new()?;
+        copy.write_all(&contents)?;
+
+        for option in ["-l", "-lb"] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.args(["cmp", option, proc_file]).arg(copy.path());
+            cmd.assert()
+                .code(predicate::eq(1))
+                .failure()
+                .stdout(predicate::str::starts_with(format!("51 {from_byte:3o} ")))
+                .stderr(predicate::str::contains("EOF on"));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn cmp_max_bytes_limits() -> Result<(), Box<dyn std::error::Error>> {
+        let mut a = NamedTempFile::new()?;
+        a.write_all(b"abc")?;
+        let mut b = NamedTempFile::new()?;
+        b.write_all(b"xbcd")?;
+
+        // -n 0 compares nothing, so the inputs are equal whatever they hold,
+        // even in quiet mode where sizes could otherwise settle it
+        for options in [vec![], vec!["-l"], vec!["-s"], vec!["-b"]] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.arg("cmp").args(&options).arg("-n").arg("0");
+            cmd.arg(a.path()).arg(b.path());
+            cmd.assert()
+                .code(predicate::eq(0))
+                .success()
+                .stdout(predicate::str::is_empty())
+                .stderr(predicate::str::is_empty());
+        }
+
+        // Quiet mode only decides by size what --bytes and the skips leave
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.args(["cmp", "-s", "-i", "1", "-n", "2"]);
+        cmd.arg(a.path()).arg(b.path());
+        cmd.assert().code(predicate::eq(0)).success();
+
+        // A count too large for a usize means no limit at all
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.args(["cmp", "-l", "-n", "99999999999999999999999999"]);
+        cmd.arg(a.path()).arg(b.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq("1 141 170\n"))
+            .stderr(predicate::str::contains("EOF on"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cmp_verbose_partial_block() -> Result<(), Box<dyn std::error::Error>> {
+        // Two full 8 KiB blocks and a partial one, with differences on both
+        // sides of the first block boundary and on the very last byte
+        let mut a = vec![b'a'; 2 * 8192 + 5];
+        for i in (99..a.len()).step_by(100) {
+            a[i] = b'\n';
+        }
+        *a.last_mut().unwrap() = b'\n';
+        let mut b = a.clone();
+        b[8191] = b'b';
+        b[8192] = b'b';
+        *b.last_mut().unwrap() = b'b';
+        let mut a_file = NamedTempFile::new()?;
+        a_file.write_all(&a)?;
+        let mut b_file = NamedTempFile::new()?;
+        b_file.write_all(&b)?;
+
+        let listing = " 8192 141 142\n 8193 141 142\n16389  12 142\n";
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("cmp")
+            .arg("-l")
+            .arg(a_file.path())
+            .arg(b_file.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stderr(predicate::str::is_empty())
+            .stdout(predicate::eq(listing));
+
+        // Reading one side from a pipe changes neither the numbers nor their width
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("cmp").arg("-l").arg("-").arg(b_file.path());
+        cmd.write_stdin(a.clone());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stderr(predicate::str::is_empty())
+            .stdout(predicate::eq(listing));
+
+        // The width only has to fit the bytes left past the skip
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("cmp").arg("-l").arg("-i").arg("16000");
+        cmd.arg(a_file.path()).arg(b_file.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq("389  12 142\n"));
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("LC_ALL", "C");
+        cmd.arg("cmp").arg(a_file.path()).arg(b_file.path());
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::str::ends_with(" differ: char 8192, line 82\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cmp_print_bytes_locale() -> Result<(), Box<dyn std::error::Error>> {
+        let mut a = NamedTempFile::new()?;
+        a.write_all(b"a\xe9b")?;
+        let mut b = NamedTempFile::new()?;
+        b.write_all(b"a\xffb")?;
+
+        // High bytes use the M- notation in every locale, unset included
+        for locale in [
+            None,
+            Some("C"),
+            Some("POSIX"),
+            Some("C.UTF-8"),
+            Some("en_US.UTF-8"),
+        ] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.env_remove("LC_ALL")
+                .env_remove("LC_MESSAGES")
+                .env_remove("LANG");
+            if let Some(locale) = locale {
+                cmd.env("LC_ALL", locale);
+            }
+            cmd.arg("cmp").arg("-b").arg(a.path()).arg(b.path());
+            cmd.assert()
+                .code(predicate::eq(1))
+                .failure()
+                .stdout(predicate::str::ends_with(
+                    " differ: byte 2, line 1 is 351 M-i 377 M-^?\n",
+                ));
+
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.env_remove("LC_ALL")
+                .env_remove("LC_MESSAGES")
+                .env_remove("LANG");
+            if let Some(locale) = locale {
+                cmd.env("LC_ALL", locale);
+            }
+            cmd.arg("cmp")
+                .arg("-l")
+                .arg("-b")
+                .arg(a.path())
+                .arg(b.path());
+            cmd.assert()
+                .code(predicate::eq(1))
+                .failure()
+                .stdout(predicate::eq("2 351 M-i  377 M-^?\n"));
+        }
+
         Ok(())
     }
 
     #[test]
-    fn cmp_stdin() -> Result<(), Box<dyn std::error::Error>> {
+    fn cmp_max_bytes() -> Result<(), Box<dyn std::error::Error>> {
         let tmp_dir = tempdir()?;
 
         let a_path = tmp_dir.path().join("a");
         let mut a = File::create(&a_path).unwrap();
-        a.write_all(b"a\n").unwrap();
+        a.write_all(b"abc efg ijkl\n").unwrap();
+
+        let b_path = tmp_dir.path().join("b");
+        let mut b = File::create(&b_path).unwrap();
+        b.write_all(b"abcdefghijkl\n").unwrap();
 
         let mut cmd = Command::cargo_bin("diffutils")?;
         cmd.arg("cmp");
-        cmd.arg(&a_path);
-        cmd.write_stdin("a\n");
+        cmd.arg("-l");
+        cmd.arg("-b");
+        cmd.arg("-n");
+        cmd.arg("3");
+        cmd.arg(&a_path).arg(&b_path);
         cmd.assert()
             .code(predicate::eq(0))
             .success()
@@ -384,241 +2250,283 @@ mod cmp {
             .stdout(predicate::str::is_empty());
 
         let mut cmd = Command::cargo_bin("diffutils")?;
-        cmd.env("LC_ALL", "C");
         cmd.arg("cmp");
-        cmd.arg(&a_path);
-        cmd.write_stdin("b\n");
+        cmd.arg("-l");
+        cmd.arg("-b");
+        cmd.arg("-n");
+        cmd.arg("4");
+        cmd.arg(&a_path).arg(&b_path);
         cmd.assert()
             .code(predicate::eq(1))
             .failure()
             .stderr(predicate::str::is_empty())
-            .stdout(predicate::str::ends_with(" - differ: char 1, line 1\n"));
-
-        Ok(())
-    }
-
-    #[test]
-    fn cmp_equal_files() -> Result<(), Box<dyn std::error::Error>> {
-        let tmp_dir = tempdir()?;
-
-        let a_path = tmp_dir.path().join("a");
-        let mut a = File::create(&a_path).unwrap();
-        a.write_all(b"a\n").unwrap();
-
-        let b_path = tmp_dir.path().join("b");
-        let mut b = File::create(&b_path).unwrap();
-        b.write_all(b"a\n").unwrap();
+            .stdout(predicate::eq("4  40      144 d\n"));
 
         let mut cmd = Command::cargo_bin("diffutils")?;
         cmd.arg("cmp");
+        cmd.arg("-l");
+        cmd.arg("-b");
+        cmd.arg("-n");
+        cmd.arg("13");
         cmd.arg(&a_path).arg(&b_path);
         cmd.assert()
-            .code(predicate::eq(0))
-            .success()
+            .code(predicate::eq(1))
+            .failure()
             .stderr(predicate::str::is_empty())
-            .stdout(predicate::str::is_empty());
+            .stdout(predicate::eq(" 4  40      144 d\n 8  40      150 h\n"));
+
+        // Comparison stops at the limit, before the EOF on the shorter file
+        let c_path = tmp_dir.path().join("c");
+        let mut c = File::create(&c_path).unwrap();
+        c.write_all(b"abcdefgh").unwrap();
 
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("cmp");
+        cmd.arg("-l");
+        cmd.arg("-n");
+        cmd.arg("4");
+        cmd.arg(&a_path).arg(&c_path);
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stderr(predicate::str::is_empty())
+            .stdout(predicate::eq("4  40 144\n"));
         Ok(())
     }
 
     #[test]
-    fn cmp_one_file_empty() -> Result<(), Box<dyn std::error::Error>> {
+    fn cmp_strip_trailing_cr() -> Result<(), Box<dyn std::error::Error>> {
         let tmp_dir = tempdir()?;
 
         let a_path = tmp_dir.path().join("a");
         let mut a = File::create(&a_path).unwrap();
-        a.write_all(b"a\n").unwrap();
+        a.write_all(b"a\r\nb\n").unwrap();
 
         let b_path = tmp_dir.path().join("b");
-        let _ = File::create(&b_path).unwrap();
+        let mut b = File::create(&b_path).unwrap();
+        b.write_all(b"a\nb\n").unwrap();
 
         let mut cmd = Command::cargo_bin("diffutils")?;
         cmd.arg("cmp");
         cmd.arg(&a_path).arg(&b_path);
+        cmd.assert().code(predicate::eq(1)).failure();
+
+        for args in [vec![], vec!["-s"], vec!["-l"]] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.arg("cmp");
+            cmd.arg("--strip-trailing-cr");
+            cmd.args(args);
+            cmd.arg(&a_path).arg(&b_path);
+            cmd.assert()
+                .code(predicate::eq(0))
+                .success()
+                .stdout(predicate::str::is_empty())
+                .stderr(predicate::str::is_empty());
+        }
+
+        // A carriage return not followed by a newline is still compared
+        let c_path = tmp_dir.path().join("c");
+        let mut c = File::create(&c_path).unwrap();
+        c.write_all(b"a\rb\n").unwrap();
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.env("LC_ALL", "C");
+        cmd.arg("cmp");
+        cmd.arg("--strip-trailing-cr");
+        cmd.arg(&c_path).arg(&b_path);
         cmd.assert()
             .code(predicate::eq(1))
             .failure()
-            .stderr(predicate::str::contains(" EOF on "))
-            .stderr(predicate::str::ends_with(" which is empty\n"));
+            .stdout(predicate::str::ends_with(" differ: char 2, line 1\n"));
+
+        // Byte numbers leave out the stripped carriage returns: the X is the
+        // 7th byte of d but the 5th byte compared
+        let d_path = tmp_dir.path().join("d");
+        std::fs::write(&d_path, b"a\r\nb\r\nX")?;
+        let e_path = tmp_dir.path().join("e");
+        std::fs::write(&e_path, b"a\nb\nY")?;
+        for (option, expected) in [("", " differ: char 5, line 3\n"), ("-l", "5 130 131\n")] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.env("LC_ALL", "C");
+            cmd.args(["cmp", "--strip-trailing-cr"]);
+            if !option.is_empty() {
+                cmd.arg(option);
+            }
+            cmd.arg(&d_path).arg(&e_path);
+            cmd.assert()
+                .code(predicate::eq(1))
+                .failure()
+                .stdout(predicate::str::ends_with(expected));
+        }
 
         Ok(())
     }
 
     #[test]
-    fn cmp_immediate_difference() -> Result<(), Box<dyn std::error::Error>> {
+    fn cmp_total() -> Result<(), Box<dyn std::error::Error>> {
         let tmp_dir = tempdir()?;
 
         let a_path = tmp_dir.path().join("a");
         let mut a = File::create(&a_path).unwrap();
-        a.write_all(b"abc\n").unwrap();
+        a.write_all(b"abc efg ijkl\n").unwrap();
 
         let b_path = tmp_dir.path().join("b");
         let mut b = File::create(&b_path).unwrap();
-        b.write_all(b"bcd\n").unwrap();
+        b.write_all(b"abcdefghijkl\n").unwrap();
 
+        let c_path = tmp_dir.path().join("c");
+        let mut c = File::create(&c_path).unwrap();
+        c.write_all(b"abcdefgh").unwrap();
+
+        // Off by default
         let mut cmd = Command::cargo_bin("diffutils")?;
-        cmd.env("LC_ALL", "C");
         cmd.arg("cmp");
+        cmd.arg("-l");
         cmd.arg(&a_path).arg(&b_path);
         cmd.assert()
             .code(predicate::eq(1))
             .failure()
-            .stdout(predicate::str::ends_with(" differ: char 1, line 1\n"));
+            .stderr(predicate::str::is_empty());
 
         let mut cmd = Command::cargo_bin("diffutils")?;
-        cmd.env("LC_ALL", "C");
         cmd.arg("cmp");
-        cmd.arg("-b");
+        cmd.arg("-l");
+        cmd.arg("--total");
         cmd.arg(&a_path).arg(&b_path);
         cmd.assert()
             .code(predicate::eq(1))
             .failure()
-            .stderr(predicate::str::is_empty())
-            .stdout(predicate::str::ends_with(
-                " differ: byte 1, line 1 is 141 a 142 b\n",
+            .stdout(predicate::eq(" 4  40 144\n 8  40 150\n"))
+            .stderr(predicate::str::ends_with(
+                ": 13 bytes compared, 2 differing\n",
             ));
 
+        // The trailer follows the EOF message
         let mut cmd = Command::cargo_bin("diffutils")?;
-        cmd.env("LC_ALL", "C");
         cmd.arg("cmp");
         cmd.arg("-l");
-        cmd.arg(&a_path).arg(&b_path);
+        cmd.arg("-T");
+        cmd.arg(&a_path).arg(&c_path);
         cmd.assert()
             .code(predicate::eq(1))
             .failure()
-            .stderr(predicate::str::is_empty())
-            .stdout(predicate::eq("1 141 142\n2 142 143\n3 143 144\n"));
+            .stderr(predicate::str::contains(" after byte 8\n"))
+            .stderr(predicate::str::ends_with(
+                ": 8 bytes compared, 2 differing\n",
+            ));
 
         let mut cmd = Command::cargo_bin("diffutils")?;
-        cmd.env("LC_ALL", "C");
         cmd.arg("cmp");
         cmd.arg("-l");
-        cmd.arg("-b");
+        cmd.arg("-T");
+        cmd.arg("-n");
+        cmd.arg("6");
         cmd.arg(&a_path).arg(&b_path);
         cmd.assert()
             .code(predicate::eq(1))
             .failure()
-            .stderr(predicate::str::is_empty())
-            .stdout(predicate::eq(
-                "1 141 a    142 b\n2 142 b    143 c\n3 143 c    144 d\n",
+            .stderr(predicate::str::ends_with(
+                ": 6 bytes compared, 1 differing\n",
+            ));
+
+        // Without -l, comparison stops at the first difference
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("cmp");
+        cmd.arg("-T");
+        cmd.arg(&a_path).arg(&b_path);
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stderr(predicate::str::ends_with(
+                ": 4 bytes compared, 1 differing\n",
             ));
 
         Ok(())
     }
 
     #[test]
-    fn cmp_newline_difference() -> Result<(), Box<dyn std::error::Error>> {
+    fn cmp_quiet_first_block_difference() -> Result<(), Box<dyn std::error::Error>> {
         let tmp_dir = tempdir()?;
 
+        // Equal sizes, so the size check cannot tell the files apart
+        let mut a_bytes = vec![b'a'; 64 * 1024];
         let a_path = tmp_dir.path().join("a");
-        let mut a = File::create(&a_path).unwrap();
-        a.write_all(b"abc\ndefg").unwrap();
-
+        File::create(&a_path)?.write_all(&a_bytes)?;
+        a_bytes[10] = b'b';
         let b_path = tmp_dir.path().join("b");
-        let mut b = File::create(&b_path).unwrap();
-        b.write_all(b"abc\ndef\ng").unwrap();
+        File::create(&b_path)?.write_all(&a_bytes)?;
 
         let mut cmd = Command::cargo_bin("diffutils")?;
-        cmd.env("LC_ALL", "C");
         cmd.arg("cmp");
+        cmd.arg("-s");
         cmd.arg(&a_path).arg(&b_path);
         cmd.assert()
             .code(predicate::eq(1))
             .failure()
             .stderr(predicate::str::is_empty())
-            .stdout(predicate::str::ends_with(" differ: char 8, line 2\n"));
+            .stdout(predicate::str::is_empty());
 
+        // A difference past --bytes is still ignored
         let mut cmd = Command::cargo_bin("diffutils")?;
-        cmd.env("LC_ALL", "C");
         cmd.arg("cmp");
-        cmd.arg("-b");
+        cmd.arg("-s");
+        cmd.arg("-n");
+        cmd.arg("10");
         cmd.arg(&a_path).arg(&b_path);
         cmd.assert()
-            .code(predicate::eq(1))
-            .failure()
+            .code(predicate::eq(0))
+            .success()
             .stderr(predicate::str::is_empty())
-            .stdout(predicate::str::ends_with(
-                " differ: byte 8, line 2 is 147 g  12 ^J\n",
-            ));
-
-        let mut cmd = Command::cargo_bin("diffutils")?;
-        cmd.env("LC_ALL", "C");
-        cmd.arg("cmp");
-        cmd.arg("-l");
-        cmd.arg(&a_path).arg(&b_path);
-        cmd.assert()
-            .code(predicate::eq(1))
-            .failure()
-            .stdout(predicate::str::starts_with("8 147  12\n"))
-            .stderr(predicate::str::contains(" EOF on"))
-            .stderr(predicate::str::ends_with(" after byte 8\n"));
-
-        let mut cmd = Command::cargo_bin("diffutils")?;
-        cmd.env("LC_ALL", "C");
-        cmd.arg("cmp");
-        cmd.arg("-b");
-        cmd.arg("-l");
-        cmd.arg(&a_path).arg(&b_path);
-        cmd.assert()
-            .code(predicate::eq(1))
-            .failure()
-            .stdout(predicate::str::starts_with("8 147 g     12 ^J\n"))
-            .stderr(predicate::str::contains(" EOF on"))
-            .stderr(predicate::str::ends_with(" after byte 8\n"));
+            .stdout(predicate::str::is_empty());
 
         Ok(())
     }
 
     #[test]
-    fn cmp_max_bytes() -> Result<(), Box<dyn std::error::Error>> {
+    fn cmp_skip_to_eof() -> Result<(), Box<dyn std::error::Error>> {
         let tmp_dir = tempdir()?;
 
-        let a_path = tmp_dir.path().join("a");
-        let mut a = File::create(&a_path).unwrap();
-        a.write_all(b"abc efg ijkl\n").unwrap();
+        let short_path = tmp_dir.path().join("short");
+        File::create(&short_path)?.write_all(b"abc")?;
+        let long_path = tmp_dir.path().join("long");
+        File::create(&long_path)?.write_all(b"abcdef")?;
+        let other_path = tmp_dir.path().join("other");
+        File::create(&other_path)?.write_all(b"xyz")?;
 
-        let b_path = tmp_dir.path().join("b");
-        let mut b = File::create(&b_path).unwrap();
-        b.write_all(b"abcdefghijkl\n").unwrap();
+        // Skipping the whole of the shorter file leaves it empty
+        let short_str = short_path.to_string_lossy();
+        for (a, b) in [(&short_path, &long_path), (&long_path, &short_path)] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.arg("cmp");
+            cmd.arg("-i").arg("3").arg(a).arg(b);
+            cmd.assert()
+                .code(predicate::eq(1))
+                .failure()
+                .stdout(predicate::str::is_empty())
+                .stderr(predicate::str::ends_with(format!(
+                    ": EOF on '{short_str}' which is empty\n"
+                )));
+        }
 
+        // Both files skipped to their end are equal
         let mut cmd = Command::cargo_bin("diffutils")?;
         cmd.arg("cmp");
-        cmd.arg("-l");
-        cmd.arg("-b");
-        cmd.arg("-n");
-        cmd.arg("3");
-        cmd.arg(&a_path).arg(&b_path);
+        cmd.arg("-i").arg("3").arg(&short_path).arg(&other_path);
         cmd.assert()
             .code(predicate::eq(0))
             .success()
-            .stderr(predicate::str::is_empty())
-            .stdout(predicate::str::is_empty());
+            .stdout(predicate::str::is_empty())
+            .stderr(predicate::str::is_empty());
 
         let mut cmd = Command::cargo_bin("diffutils")?;
         cmd.arg("cmp");
-        cmd.arg("-l");
-        cmd.arg("-b");
-        cmd.arg("-n");
-        cmd.arg("4");
-        cmd.arg(&a_path).arg(&b_path);
+        cmd.arg("-i").arg("3:6").arg(&short_path).arg(&long_path);
         cmd.assert()
-            .code(predicate::eq(1))
-            .failure()
-            .stderr(predicate::str::is_empty())
-            .stdout(predicate::eq("4  40      144 d\n"));
+            .code(predicate::eq(0))
+            .success()
+            .stdout(predicate::str::is_empty())
+            .stderr(predicate::str::is_empty());
 
-        let mut cmd = Command::cargo_bin("diffutils")?;
-        cmd.arg("cmp");
-        cmd.arg("-l");
-        cmd.arg("-b");
-        cmd.arg("-n");
-        cmd.arg("13");
-        cmd.arg(&a_path).arg(&b_path);
-        cmd.assert()
-            .code(predicate::eq(1))
-            .failure()
-            .stderr(predicate::str::is_empty())
-            .stdout(predicate::eq(" 4  40      144 d\n 8  40      150 h\n"));
         Ok(())
     }
 
@@ -800,6 +2708,57 @@ mod cmp {
         Ok(())
     }
 
+    #[test]
+    #[cfg(not(windows))]
+    fn cmp_sparse_file() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::{Seek, SeekFrom};
+
+        let tmp_dir = tempdir()?;
+
+        // A file with a hole in the middle and at the end, which read as zeros
+        let a_path = tmp_dir.path().join("a");
+        let mut a = File::create(&a_path).unwrap();
+        a.write_all(b"abc").unwrap();
+        a.seek(SeekFrom::Start(1024 * 1024)).unwrap();
+        a.write_all(b"def").unwrap();
+        a.set_len(4 * 1024 * 1024).unwrap();
+
+        let mut content = vec![0; 4 * 1024 * 1024];
+        content[..3].copy_from_slice(b"abc");
+        content[1024 * 1024..1024 * 1024 + 3].copy_from_slice(b"def");
+        let b_path = tmp_dir.path().join("b");
+        let mut b = File::create(&b_path).unwrap();
+        b.write_all(&content).unwrap();
+
+        for args in [vec![], vec!["-s"], vec!["-l"]] {
+            let mut cmd = Command::cargo_bin("diffutils")?;
+            cmd.arg("cmp");
+            cmd.args(args);
+            cmd.arg(&a_path).arg(&b_path);
+            cmd.assert()
+                .code(predicate::eq(0))
+                .success()
+                .stdout(predicate::str::is_empty())
+                .stderr(predicate::str::is_empty());
+        }
+
+        // A difference past the first hole is still found
+        content[3 * 1024 * 1024] = b'x';
+        let mut b = File::create(&b_path).unwrap();
+        b.write_all(&content).unwrap();
+
+        let mut cmd = Command::cargo_bin("diffutils")?;
+        cmd.arg("cmp");
+        cmd.arg("-l");
+        cmd.arg(&a_path).arg(&b_path);
+        cmd.assert()
+            .code(predicate::eq(1))
+            .failure()
+            .stdout(predicate::eq("3145729   0 170\n"));
+
+        Ok(())
+    }
+
     #[test]
     #[cfg(not(windows))]
     fn cmp_fast_paths() -> Result<(), Box<dyn std::error::Error>> {